@@ -21,22 +21,7 @@ use embedded_graphics::{
 };
 use embedded_hal_bus::spi::ExclusiveDevice;
 
-use st7920::ST7920;
-
-struct NoPin();
-
-impl embedded_hal::digital::ErrorType for NoPin {
-    type Error = core::convert::Infallible;
-}
-
-impl embedded_hal::digital::OutputPin for NoPin {
-    fn set_low(&mut self) -> Result<(), Self::Error> {
-        Ok(())
-    }
-    fn set_high(&mut self) -> Result<(), Self::Error> {
-        Ok(())
-    }
-}
+use st7920::{CsPolarity, NoPin, ST7920Builder};
 
 #[entry]
 fn main() -> ! {
@@ -64,9 +49,13 @@ fn main() -> ! {
             hal::time::Hertz::kHz(600),
             &clocks,
         );
-        let spidev = ExclusiveDevice::new_no_delay(spi, NoPin());
+        let spidev =
+            ExclusiveDevice::new_no_delay(spi, NoPin::<core::convert::Infallible>::default());
 
-        let mut disp = ST7920::new(spidev, reset, Some(cs), false);
+        let mut disp = ST7920Builder::<_, NoPin, NoPin>::new(spidev)
+            .reset(reset)
+            .cs(cs, CsPolarity::ActiveHigh)
+            .build();
 
         disp.init(&mut delay).expect("could not init display");
         disp.clear(&mut delay).expect("could not clear display");
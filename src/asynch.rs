@@ -0,0 +1,727 @@
+//! Async driver on top of [`embedded_hal_async`], enabled by the `async` feature.
+//!
+//! Drawing to the buffer is shared with the blocking [`crate::ST7920`],
+//! only the transfers to the display are async.
+
+use core::ops::{Deref, DerefMut};
+
+use embedded_hal::digital::{self, OutputPin};
+use embedded_hal_async::delay::DelayNs;
+use embedded_hal_async::spi::SpiDevice;
+
+use crate::interface::{command_bytes, encode_data, encode_row, ROW_TRANSFER_SIZE};
+use crate::layout::Geometry;
+use crate::scroll::GDRAM_HEIGHT;
+use crate::sequence::{graphics_address_sequence, Step, GRAPHICS_SEQUENCE};
+use crate::{
+    CsPolarity, Error, Instruction, Layout, Layout128x64, NoPin, Timing, Window, GDRAM_ROW_SIZE,
+};
+
+/// Async ST7920 driver using an SPI connection.
+///
+/// Buffer access and drawing are available through `Deref` to [`crate::ST7920`].
+/// Only graphics mode is supported.
+pub struct ST7920<
+    SPI,
+    RST,
+    CS,
+    L: Layout = Layout128x64,
+    B = <L as Layout>::Buffer,
+    S = <L as Layout>::Shadow,
+> {
+    inner: crate::ST7920<SPI, RST, CS, L, B, S>,
+}
+
+impl<SPI, RST, CS, PinError, SPIError> ST7920<SPI, RST, CS>
+where
+    SPI: SpiDevice<Error = SPIError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+{
+    /// Create a new async driver instance that uses SPI connection.
+    pub fn new(spi: SPI, rst: RST, cs: Option<CS>, flip: bool) -> Self {
+        Self::new_with_layout(spi, rst, cs, flip, Layout128x64)
+    }
+}
+
+impl<SPI, RST, PinError, SPIError> ST7920<SPI, RST, NoPin<PinError>>
+where
+    SPI: SpiDevice<Error = SPIError>,
+    RST: OutputPin<Error = PinError>,
+    PinError: digital::Error,
+{
+    /// Create a new async driver instance without a CS pin.
+    pub fn new_without_cs(spi: SPI, rst: RST, flip: bool) -> Self {
+        Self::new(spi, rst, None, flip)
+    }
+}
+
+impl<SPI, RST, CS, L, PinError, SPIError> ST7920<SPI, RST, CS, L>
+where
+    SPI: SpiDevice<Error = SPIError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+{
+    /// Create a new async driver instance for a panel with the given [`Layout`].
+    pub fn new_with_layout(spi: SPI, rst: RST, cs: Option<CS>, flip: bool, layout: L) -> Self {
+        ST7920 {
+            inner: crate::ST7920::new_with_layout(spi, rst, cs, flip, layout),
+        }
+    }
+}
+
+impl<SPI, RST, CS, L, B, PinError, SPIError> ST7920<SPI, RST, CS, L, B>
+where
+    SPI: SpiDevice<Error = SPIError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Create a new async driver instance drawing to the given buffer
+    ///
+    /// See [`crate::ST7920::new_with_buffer`].
+    pub fn new_with_buffer(
+        spi: SPI,
+        rst: RST,
+        cs: Option<CS>,
+        flip: bool,
+        layout: L,
+        buffer: B,
+    ) -> Self {
+        ST7920 {
+            inner: crate::ST7920::new_with_buffer(spi, rst, cs, flip, layout, buffer),
+        }
+    }
+}
+
+impl<SPI, RST, CS, L, B, S, PinError, SPIError> ST7920<SPI, RST, CS, L, B, S>
+where
+    SPI: SpiDevice<Error = SPIError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub(crate) fn from_inner(inner: crate::ST7920<SPI, RST, CS, L, B, S>) -> Self {
+        ST7920 { inner }
+    }
+
+    /// Use a different [`Timing`] profile, e.g. for slower clones of the controller
+    pub fn with_timing(self, timing: Timing) -> Self {
+        ST7920 {
+            inner: self.inner.with_timing(timing),
+        }
+    }
+
+    /// Use a different [`CsPolarity`], e.g. behind an inverting level shifter
+    pub fn with_cs_polarity(self, polarity: CsPolarity) -> Self {
+        ST7920 {
+            inner: self.inner.with_cs_polarity(polarity),
+        }
+    }
+
+    /// Destroy the driver and return the SPI device, reset pin and CS pin
+    pub fn release(self) -> (SPI, Option<RST>, Option<CS>) {
+        self.inner.release()
+    }
+
+    /// Destroy the driver and return the SPI device, pins and buffer
+    pub fn free(self) -> (SPI, Option<RST>, Option<CS>, B) {
+        self.inner.free()
+    }
+
+    async fn enable_cs<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        if let Some(cs) = self.inner.cs.as_mut() {
+            match self.inner.cs_polarity {
+                CsPolarity::ActiveHigh => cs.set_high(),
+                CsPolarity::ActiveLow => cs.set_low(),
+            }
+            .map_err(Error::Pin)?;
+            delay.delay_us(self.inner.timing.cs_us).await;
+        }
+        Ok(())
+    }
+
+    async fn disable_cs<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        if let Some(cs) = self.inner.cs.as_mut() {
+            delay.delay_us(self.inner.timing.cs_us).await;
+            match self.inner.cs_polarity {
+                CsPolarity::ActiveHigh => cs.set_low(),
+                CsPolarity::ActiveLow => cs.set_high(),
+            }
+            .map_err(Error::Pin)?;
+        }
+        Ok(())
+    }
+
+    async fn write_command(
+        &mut self,
+        command: Instruction,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.write_command_param(command, 0).await
+    }
+
+    async fn write_command_param(
+        &mut self,
+        command: Instruction,
+        param: u8,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.inner
+            .interface
+            .write(&command_bytes(command.opcode() | param))
+            .await
+            .map_err(Error::Comm)?;
+        self.inner.command_sent(command);
+        Ok(())
+    }
+
+    /// Send the instructions of a sequence, see [`crate::ST7920`].
+    async fn run<Delay: DelayNs>(
+        &mut self,
+        sequence: &[Step],
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        for &step in sequence {
+            match step {
+                Step::Command(command, param) => self.write_command_param(command, param).await?,
+                Step::Basic => self.select_basic().await?,
+                Step::Extended => self.select_extended(delay).await?,
+                Step::Wait(us) => delay.delay_us(us).await,
+                Step::Pause => self.flush_pause(delay).await,
+            }
+        }
+        Ok(())
+    }
+
+    /// Select the basic instruction set, see [`crate::ST7920`].
+    async fn select_basic(&mut self) -> Result<(), Error<SPIError, PinError>> {
+        if self.inner.extended {
+            self.write_command(Instruction::BasicFunction).await?;
+        }
+        Ok(())
+    }
+
+    /// Select the extended instruction set, setting the graphics display bit again
+    /// if the mode shows graphics.
+    async fn select_extended<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        if !self.inner.extended {
+            self.write_command(Instruction::ExtendedFunction).await?;
+            if self.inner.mode.has_graphics() {
+                delay.delay_us(self.inner.timing.command_us).await;
+                self.write_command(Instruction::GraphicsOn).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait between the instructions of a flush, see [`Timing::flush_us`].
+    async fn flush_pause<Delay: DelayNs>(&self, delay: &mut Delay) {
+        if self.inner.timing.flush_us > 0 {
+            delay.delay_us(self.inner.timing.flush_us).await;
+        }
+    }
+
+    async fn hard_reset<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        let reset_ms = self.inner.timing.reset_ms;
+        match self.inner.rst.as_mut() {
+            Some(rst) => {
+                rst.set_low().map_err(Error::Pin)?;
+                delay.delay_ms(reset_ms).await;
+                rst.set_high().map_err(Error::Pin)?;
+                delay.delay_ms(reset_ms).await;
+            }
+            None => {
+                delay.delay_ms(reset_ms).await;
+                self.run(&self.inner.soft_reset_sequence(), delay).await?;
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    async fn do_init<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.hard_reset(delay).await?;
+        self.inner.reset_state();
+        self.run(&self.inner.init_sequence(), delay).await
+    }
+
+    /// Initialize the display controller
+    pub async fn init<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.enable_cs(delay).await?;
+        let result = self.do_init(delay).await;
+        self.disable_cs(delay).await?;
+        result
+    }
+
+    /// Put the controller into standby to save power
+    ///
+    /// See [`crate::ST7920::sleep`].
+    pub async fn sleep<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        if self.inner.asleep {
+            return Ok(());
+        }
+        self.enable_cs(delay).await?;
+        let result = self.do_sleep(delay).await;
+        self.disable_cs(delay).await?;
+        result
+    }
+
+    async fn do_sleep<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.run(&self.inner.sleep_sequence(), delay).await?;
+        self.inner.asleep = true;
+        Ok(())
+    }
+
+    /// Wake the controller up from standby
+    ///
+    /// See [`crate::ST7920::wake`].
+    pub async fn wake<Delay: DelayNs>(
+        &mut self,
+        flush: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        if !self.inner.asleep {
+            return Ok(());
+        }
+        self.enable_cs(delay).await?;
+        let result = self.do_wake(delay).await;
+        self.disable_cs(delay).await?;
+        result?;
+        if flush {
+            self.inner.invalidate_shadow();
+            self.flush(delay).await
+        } else {
+            self.flush_dirty(delay).await
+        }
+    }
+
+    async fn do_wake<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.run(&self.inner.wake_sequence(), delay).await
+    }
+
+    /// Turn the display on or off
+    ///
+    /// See [`crate::ST7920::display_on`].
+    pub async fn display_on<Delay: DelayNs>(
+        &mut self,
+        on: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.enable_cs(delay).await?;
+        let result = self.do_display_on(on, delay).await;
+        self.disable_cs(delay).await?;
+        result
+    }
+
+    async fn do_display_on<Delay: DelayNs>(
+        &mut self,
+        on: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.run(&self.inner.display_on_sequence(on), delay).await?;
+        self.inner.display_on = on;
+        Ok(())
+    }
+
+    /// Scroll the display by `offset` GDRAM rows, modulo 64
+    ///
+    /// See [`crate::ST7920::set_scroll`].
+    pub async fn set_scroll<Delay: DelayNs>(
+        &mut self,
+        offset: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.inner.scroll = offset % GDRAM_HEIGHT;
+        if !self.inner.scroll_enabled {
+            return Ok(());
+        }
+        self.enable_cs(delay).await?;
+        let result = self.write_scroll(delay).await;
+        self.disable_cs(delay).await?;
+        result
+    }
+
+    /// Enable or disable the hardware scroll
+    pub async fn enable_scroll<Delay: DelayNs>(
+        &mut self,
+        enable: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.enable_cs(delay).await?;
+        let result = self.do_enable_scroll(enable, delay).await;
+        self.disable_cs(delay).await?;
+        result
+    }
+
+    async fn do_enable_scroll<Delay: DelayNs>(
+        &mut self,
+        enable: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.run(&self.inner.enable_scroll_sequence(enable), delay)
+            .await?;
+        self.inner.scroll_enabled = enable;
+        Ok(())
+    }
+
+    async fn write_scroll<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.run(&self.inner.scroll_sequence(), delay).await
+    }
+
+    /// Send GDRAM rows of the buffer to other GDRAM rows, e.g. off-screen
+    ///
+    /// See [`crate::ST7920::flush_rows_to`].
+    pub async fn flush_rows_to<Delay: DelayNs>(
+        &mut self,
+        source: u8,
+        count: u8,
+        target: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        let count = count.min(L::GDRAM_ROWS.saturating_sub(source));
+        if count == 0 || !self.inner.can_flush() {
+            return Ok(());
+        }
+        self.enable_cs(delay).await?;
+        let result = self.do_flush_rows_to(source, count, target, delay).await;
+        self.disable_cs(delay).await?;
+        result
+    }
+
+    async fn do_flush_rows_to<Delay: DelayNs>(
+        &mut self,
+        source: u8,
+        count: u8,
+        target: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.run(&GRAPHICS_SEQUENCE, delay).await?;
+
+        let mut row = [0; GDRAM_ROW_SIZE];
+        for i in 0..count {
+            let (address, len) = self
+                .inner
+                .row_to(&mut row, source + i, target.wrapping_add(i));
+            self.write_graphics(address, &row[..len], delay).await?;
+        }
+        self.inner.rows_written_to(count, target);
+        Ok(())
+    }
+
+    /// Clear whole display area and clears the buffer
+    pub async fn clear<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.inner.clear_buffer();
+        self.flush(delay).await
+    }
+
+    async fn write_transfer(&mut self, transfer: &[u8]) -> Result<(), Error<SPIError, PinError>> {
+        self.inner
+            .interface
+            .write(transfer)
+            .await
+            .map_err(Error::Comm)
+    }
+
+    /// Write data to GDRAM, pausing between the instructions if needed.
+    async fn write_graphics<Delay: DelayNs>(
+        &mut self,
+        address: [u8; 2],
+        data: &[u8],
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        let mut transfer = [0; ROW_TRANSFER_SIZE];
+        if self.inner.timing.flush_us == 0 {
+            let len = encode_row(&mut transfer, address, data);
+            self.write_transfer(&transfer[..len]).await
+        } else {
+            self.run(&graphics_address_sequence(address), delay).await?;
+            let len = encode_data(&mut transfer, data);
+            self.write_transfer(&transfer[..len]).await?;
+            self.flush_pause(delay).await;
+            Ok(())
+        }
+    }
+
+    #[inline]
+    async fn do_flush<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.run(&GRAPHICS_SEQUENCE, delay).await?;
+        let mut row = [0; GDRAM_ROW_SIZE];
+        for y in 0..L::GDRAM_ROWS {
+            let mut word = 0;
+            while let Some((start, end)) = self.inner.changed_words(y, word) {
+                let (address, len) = self.inner.gdram_words(&mut row, y, start, end);
+                self.write_graphics(address, &row[..len], delay).await?;
+                word = end;
+            }
+        }
+        self.inner.update_shadow(Window::full::<L>());
+        Ok(())
+    }
+
+    /// Flush buffer to update entire display
+    pub async fn flush<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        if !self.inner.can_flush() {
+            return Ok(());
+        }
+        self.enable_cs(delay).await?;
+        let result = self.do_flush(delay).await;
+        self.disable_cs(delay).await?;
+        if result.is_ok() {
+            self.inner.dirty = None;
+        }
+        result
+    }
+
+    #[inline]
+    async fn do_flush_region<Delay: DelayNs>(
+        &mut self,
+        x: u8,
+        y: u8,
+        w: u8,
+        h: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        let window = self.inner.window(x, y, w, h);
+        self.do_flush_window(window, delay).await
+    }
+
+    #[inline]
+    async fn do_flush_window<Delay: DelayNs>(
+        &mut self,
+        window: Window,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        self.run(&GRAPHICS_SEQUENCE, delay).await?;
+
+        let mut row = [0; GDRAM_ROW_SIZE];
+        for y in window.top..window.bottom {
+            let (address, len) = self.inner.window_row(&mut row, window, y);
+            self.write_graphics(address, &row[..len], delay).await?;
+        }
+        self.inner.update_shadow(window);
+        Ok(())
+    }
+
+    /// Flush only the part of the buffer changed since the last flush
+    ///
+    /// See [`crate::ST7920::flush_dirty`].
+    pub async fn flush_dirty<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        match self.inner.dirty {
+            Some(window) if self.inner.can_flush() => {
+                self.enable_cs(delay).await?;
+                let result = self.do_flush_window(window, delay).await;
+                self.disable_cs(delay).await?;
+                if result.is_ok() {
+                    self.inner.dirty = None;
+                }
+                result
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Flush buffer to update region of the display
+    ///
+    /// If the region is completely off screen,
+    /// nothing will be done and Ok()) will be returned.
+    /// If the given width or height are too big,
+    /// width and height will be trimmed to the screen dimensions.
+    pub async fn flush_region<Delay: DelayNs>(
+        &mut self,
+        x: u8,
+        y: u8,
+        w: u8,
+        h: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>> {
+        match self.inner.clip_region(x, y, w, h) {
+            Some((w, h)) if self.inner.can_flush() => {
+                self.enable_cs(delay).await?;
+                let result = self.do_flush_region(x, y, w, h, delay).await;
+                self.disable_cs(delay).await?;
+                result
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<SPI, RST, CS, L: Layout, B, S> Deref for ST7920<SPI, RST, CS, L, B, S> {
+    type Target = crate::ST7920<SPI, RST, CS, L, B, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<SPI, RST, CS, L: Layout, B, S> DerefMut for ST7920<SPI, RST, CS, L, B, S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+#[cfg(feature = "graphics")]
+use embedded_graphics::{draw_target::DrawTarget, pixelcolor::BinaryColor, prelude::*};
+
+#[cfg(feature = "graphics")]
+use crate::{graphics_address, Unbuffered};
+
+#[cfg(feature = "graphics")]
+impl<SPI, RST, CS, L, B, S, PinError, SPIError> OriginDimensions for ST7920<SPI, RST, CS, L, B, S>
+where
+    SPI: SpiDevice<Error = SPIError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn size(&self) -> Size {
+        self.inner.size()
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, RST, CS, L, B, S, PinError, SPIError> DrawTarget for ST7920<SPI, RST, CS, L, B, S>
+where
+    SPI: SpiDevice<Error = SPIError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    type Error = core::convert::Infallible;
+    type Color = BinaryColor;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        self.inner.draw_iter(pixels)
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, RST, CS, L, PinError, SPIError> ST7920<SPI, RST, CS, L, Unbuffered, Unbuffered>
+where
+    SPI: SpiDevice<Error = SPIError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+{
+    /// Create a new async driver instance without a buffer, see [`Unbuffered`].
+    pub fn new_unbuffered(spi: SPI, rst: RST, cs: Option<CS>, flip: bool, layout: L) -> Self {
+        ST7920 {
+            inner: crate::ST7920::new_unbuffered(spi, rst, cs, flip, layout),
+        }
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<SPI, RST, CS, L, B, S, PinError, SPIError> ST7920<SPI, RST, CS, L, B, S>
+where
+    SPI: SpiDevice<Error = SPIError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Draw directly to the display, rendering a band of rows at a time into `scratch`
+    ///
+    /// See [`crate::ST7920::draw_streamed`].
+    pub async fn draw_streamed<D, Delay>(
+        &mut self,
+        drawable: &D,
+        scratch: &mut [u8],
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>>
+    where
+        D: Drawable<Color = BinaryColor>,
+        Delay: DelayNs,
+    {
+        let rows = crate::ST7920::<SPI, RST, CS, L, B, S>::band_rows(scratch.len());
+        if !self.inner.shows_graphics() {
+            return Ok(());
+        }
+        self.enable_cs(delay).await?;
+        let result = self.do_draw_streamed(drawable, scratch, rows, delay).await;
+        self.disable_cs(delay).await?;
+        self.inner.invalidate_shadow();
+        self.inner.mark_dirty(Window::full::<L>());
+        result
+    }
+
+    async fn do_draw_streamed<D, Delay>(
+        &mut self,
+        drawable: &D,
+        scratch: &mut [u8],
+        rows: u32,
+        delay: &mut Delay,
+    ) -> Result<(), Error<SPIError, PinError>>
+    where
+        D: Drawable<Color = BinaryColor>,
+        Delay: DelayNs,
+    {
+        self.run(&GRAPHICS_SEQUENCE, delay).await?;
+
+        let mut top = 0;
+        while top < L::HEIGHT {
+            let rows = rows.min(L::HEIGHT - top);
+            let band = &mut scratch[..rows as usize * L::ROW_SIZE];
+            self.inner.render_band(drawable, band, top);
+            for (y, data) in (top..).zip(band.chunks_exact(L::ROW_SIZE)) {
+                self.write_graphics(graphics_address::<L>(0, y as u8), data, delay)
+                    .await?;
+            }
+            top += rows;
+        }
+        Ok(())
+    }
+}
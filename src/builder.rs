@@ -0,0 +1,213 @@
+//! Builder for the driver with optional pins.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use embedded_hal::digital::{self, OutputPin};
+
+use crate::{interface, CsPolarity, Layout, Layout128x64, Mode, Rotation, Timing, ST7920};
+
+/// Placeholder for a pin that is not connected.
+///
+/// The error type follows the connected pins, so the driver can be built
+/// with only some of the pins.
+#[derive(Debug)]
+pub struct NoPin<E = Infallible>(PhantomData<E>);
+
+impl<E> Default for NoPin<E> {
+    fn default() -> Self {
+        NoPin(PhantomData)
+    }
+}
+
+impl<E: digital::Error> digital::ErrorType for NoPin<E> {
+    type Error = E;
+}
+
+impl<E: digital::Error> OutputPin for NoPin<E> {
+    fn set_low(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// Builder for [`ST7920`]
+///
+/// Without a reset pin, the controller is brought to a known state by software
+/// during [`ST7920::init`]. The chip select polarity can only be given together with
+/// the pin, so invalid combinations are rejected when the code is compiled.
+///
+/// # Examples
+///
+/// ```no_run
+/// use st7920::{CsPolarity, Layout256x32, Rotation, ST7920Builder};
+///
+/// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+/// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+/// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+/// let mut st7920 = ST7920Builder::new(spi)
+///     .cs(cs, CsPolarity::ActiveLow)
+///     .layout(Layout256x32)
+///     .rotation(Rotation::Deg180)
+///     .build();
+/// st7920.init(&mut delay)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// If neither pin is connected, name the placeholders:
+///
+/// ```no_run
+/// use st7920::{NoPin, ST7920Builder};
+///
+/// # fn example(spi: impl embedded_hal::spi::SpiDevice) {
+/// let mut st7920 = ST7920Builder::<_, NoPin, NoPin>::new(spi).build();
+/// # }
+/// ```
+pub struct ST7920Builder<DI, RST = NoPin, CS = NoPin, L = Layout128x64> {
+    interface: DI,
+    rst: Option<RST>,
+    cs: Option<CS>,
+    cs_polarity: CsPolarity,
+    layout: PhantomData<L>,
+    rotation: Rotation,
+    mirror: (bool, bool),
+    timing: Timing,
+    mode: Mode,
+}
+
+impl<DI, E> ST7920Builder<DI, NoPin<E>, NoPin<E>> {
+    /// Start building a driver for a 128x64 panel without reset and CS pins.
+    pub fn new(interface: DI) -> Self {
+        ST7920Builder {
+            interface,
+            rst: None,
+            cs: None,
+            cs_polarity: CsPolarity::ActiveHigh,
+            layout: PhantomData,
+            rotation: Rotation::Deg0,
+            mirror: (false, false),
+            timing: Timing::default(),
+            mode: Mode::Graphics,
+        }
+    }
+}
+
+impl<DI, CS, L, E> ST7920Builder<DI, NoPin<E>, CS, L> {
+    /// Reset the controller with a pin instead of by software.
+    pub fn reset<RST>(self, rst: RST) -> ST7920Builder<DI, RST, CS, L> {
+        ST7920Builder {
+            interface: self.interface,
+            rst: Some(rst),
+            cs: self.cs,
+            cs_polarity: self.cs_polarity,
+            layout: self.layout,
+            rotation: self.rotation,
+            mirror: self.mirror,
+            timing: self.timing,
+            mode: self.mode,
+        }
+    }
+}
+
+impl<DI, RST, L, E> ST7920Builder<DI, RST, NoPin<E>, L> {
+    /// Drive a chip select pin with the given polarity around each transaction.
+    pub fn cs<CS>(self, cs: CS, polarity: CsPolarity) -> ST7920Builder<DI, RST, CS, L> {
+        ST7920Builder {
+            interface: self.interface,
+            rst: self.rst,
+            cs: Some(cs),
+            cs_polarity: polarity,
+            layout: self.layout,
+            rotation: self.rotation,
+            mirror: self.mirror,
+            timing: self.timing,
+            mode: self.mode,
+        }
+    }
+}
+
+impl<DI, RST, CS, L> ST7920Builder<DI, RST, CS, L> {
+    /// Drive a panel with the given [`Layout`].
+    pub fn layout<M: Layout>(self, _layout: M) -> ST7920Builder<DI, RST, CS, M> {
+        ST7920Builder {
+            interface: self.interface,
+            rst: self.rst,
+            cs: self.cs,
+            cs_polarity: self.cs_polarity,
+            layout: PhantomData,
+            rotation: self.rotation,
+            mirror: self.mirror,
+            timing: self.timing,
+            mode: self.mode,
+        }
+    }
+
+    /// Initial rotation, see [`ST7920::set_rotation`].
+    pub fn rotation(mut self, rotation: Rotation) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    /// Initial mirroring, see [`ST7920::set_mirror`].
+    pub fn mirror(mut self, x: bool, y: bool) -> Self {
+        self.mirror = (x, y);
+        self
+    }
+
+    /// Use a different [`Timing`] profile.
+    pub fn timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Initial display mode, see [`ST7920::with_mode`].
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+impl<DI, RST, CS, L, PinError, CommError> ST7920Builder<DI, RST, CS, L>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+{
+    /// Create the driver. It still has to be initialized with [`ST7920::init`].
+    pub fn build(self) -> ST7920<DI, RST, CS, L> {
+        self.build_with_buffer(L::new_buffer())
+    }
+
+    /// Create the driver drawing to the given buffer, see [`ST7920::new_with_buffer`].
+    pub fn build_with_buffer<B>(self, buffer: B) -> ST7920<DI, RST, CS, L, B>
+    where
+        B: AsRef<[u8]> + AsMut<[u8]>,
+    {
+        crate::check_buffer::<L>(buffer.as_ref());
+        let mut st7920 = ST7920::from_parts(
+            self.interface,
+            self.rst,
+            self.cs,
+            self.rotation,
+            buffer,
+            L::new_shadow(),
+        );
+        st7920.cs_polarity = self.cs_polarity;
+        st7920.set_mirror(self.mirror.0, self.mirror.1);
+        st7920.with_timing(self.timing).with_mode(self.mode)
+    }
+
+    /// Create the async driver. It still has to be initialized with [`crate::asynch::ST7920::init`].
+    #[cfg(feature = "async")]
+    pub fn build_async(self) -> crate::asynch::ST7920<DI, RST, CS, L>
+    where
+        DI: embedded_hal_async::spi::SpiDevice,
+    {
+        crate::asynch::ST7920::from_inner(self.build())
+    }
+}
@@ -0,0 +1,178 @@
+//! Encoding of text for the Chinese font ROM (HCGROM) of the controller.
+//!
+//! Full-width characters are shown from two byte GB2312 codes.
+//! [`encode`] supports printable ASCII (written as half-width characters),
+//! the GB2312 symbol rows, full-width ASCII, kana, Greek, Cyrillic and box drawing characters.
+//! Chinese ideographs are not included to keep the tables small,
+//! their codes can be passed to [`ST7920::write_gb2312`](crate::ST7920::write_gb2312) directly.
+
+use crate::Error;
+
+/// Row 1 of GB2312, codes 0xA1A1 to 0xA1FE.
+const SYMBOLS: [u16; 94] = [
+    0x3000, 0x3001, 0x3002, 0x30FB, 0x02C9, 0x02C7, 0x00A8, 0x3003, 0x3005, 0x2015, 0xFF5E, 0x2016,
+    0x2026, 0x2018, 0x2019, 0x201C, 0x201D, 0x3014, 0x3015, 0x3008, 0x3009, 0x300A, 0x300B, 0x300C,
+    0x300D, 0x300E, 0x300F, 0x3016, 0x3017, 0x3010, 0x3011, 0x00B1, 0x00D7, 0x00F7, 0x2236, 0x2227,
+    0x2228, 0x2211, 0x220F, 0x222A, 0x2229, 0x2208, 0x2237, 0x221A, 0x22A5, 0x2225, 0x2220, 0x2312,
+    0x2299, 0x222B, 0x222E, 0x2261, 0x224C, 0x2248, 0x223D, 0x221D, 0x2260, 0x226E, 0x226F, 0x2264,
+    0x2265, 0x221E, 0x2235, 0x2234, 0x2642, 0x2640, 0x00B0, 0x2032, 0x2033, 0x2103, 0xFF04, 0x00A4,
+    0xFFE0, 0xFFE1, 0x2030, 0x00A7, 0x2116, 0x2606, 0x2605, 0x25CB, 0x25CF, 0x25CE, 0x25C7, 0x25C6,
+    0x25A1, 0x25A0, 0x25B3, 0x25B2, 0x203B, 0x2192, 0x2190, 0x2191, 0x2193, 0x3013,
+];
+
+/// GB2312 code of row and cell, both starting at 1.
+fn code(row: u32, cell: u32) -> [u8; 2] {
+    [(0xA0 + row) as u8, (0xA0 + cell) as u8]
+}
+
+/// GB2312 code of a full-width character, if it is in the supported subset.
+pub fn encode_char(c: char) -> Option<[u8; 2]> {
+    let u = c as u32;
+    if let Some(i) = SYMBOLS.iter().position(|&s| s as u32 == u) {
+        return Some(code(1, i as u32 + 1));
+    }
+    let (row, cell) = match u {
+        // Full-width ASCII, except for the dollar and tilde signs found in row 1.
+        0xFFE5 => (3, 4),
+        0xFFE3 => (3, 94),
+        0xFF01..=0xFF5D => (3, u - 0xFF00),
+        // Hiragana and katakana
+        0x3041..=0x3093 => (4, u - 0x3040),
+        0x30A1..=0x30F6 => (5, u - 0x30A0),
+        // Greek, without the final sigma
+        0x0391..=0x03A9 if u != 0x03A2 => (6, u - 0x0390 - (u > 0x03A2) as u32),
+        0x03B1..=0x03C9 if u != 0x03C2 => (6, u - 0x03B0 - (u > 0x03C2) as u32 + 32),
+        // Cyrillic, with Ё sorted after Е
+        0x0401 => (7, 7),
+        0x0410..=0x042F => (7, u - 0x040F + (u > 0x0415) as u32),
+        0x0451 => (7, 55),
+        0x0430..=0x044F => (7, u - 0x042F + (u > 0x0435) as u32 + 48),
+        // Box drawing
+        0x2500..=0x254B => (9, u - 0x2500 + 4),
+        _ => return None,
+    };
+    Some(code(row, cell))
+}
+
+/// Convert a string into character codes for [`ST7920::write_gb2312`](crate::ST7920::write_gb2312).
+///
+/// Printable ASCII is encoded as one byte half-width characters,
+/// other characters as two byte GB2312 codes, see [`encode_char`].
+/// Returns the number of bytes written to `buf`,
+/// or [`Error::Unencodable`] for the first character which is not supported.
+///
+/// # Panics
+///
+/// Panics if `buf` is too small. `s.len()` bytes are always enough.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+/// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+/// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+/// # let mut st7920 = st7920::ST7920::new(spi, reset, Some(cs), false);
+/// let mut buf = [0u8; 32];
+/// let len = st7920::gb2312::encode("Temp：25℃", &mut buf)?;
+/// st7920.write_gb2312(0, 0, &buf[..len], &mut delay)?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn encode<CommError, PinError>(
+    s: &str,
+    buf: &mut [u8],
+) -> Result<usize, Error<CommError, PinError>> {
+    let mut len = 0;
+    for c in s.chars() {
+        if c == ' ' || c.is_ascii_graphic() {
+            buf[len] = c as u8;
+            len += 1;
+        } else {
+            let code = encode_char(c).ok_or(Error::Unencodable(c))?;
+            buf[len..len + 2].copy_from_slice(&code);
+            len += 2;
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols() {
+        assert_eq!(encode_char('\u{3000}'), Some([0xA1, 0xA1]));
+        assert_eq!(encode_char('℃'), Some([0xA1, 0xE6]));
+        assert_eq!(encode_char('〓'), Some([0xA1, 0xFE]));
+    }
+
+    #[test]
+    fn full_width_ascii() {
+        assert_eq!(encode_char('！'), Some([0xA3, 0xA1]));
+        assert_eq!(encode_char('Ａ'), Some([0xA3, 0xC1]));
+        assert_eq!(encode_char('｝'), Some([0xA3, 0xFD]));
+        // Dollar and tilde are in row 1, yen and macron take their places in row 3.
+        assert_eq!(encode_char('＄'), Some([0xA1, 0xE7]));
+        assert_eq!(encode_char('～'), Some([0xA1, 0xAB]));
+        assert_eq!(encode_char('￥'), Some([0xA3, 0xA4]));
+        assert_eq!(encode_char('￣'), Some([0xA3, 0xFE]));
+    }
+
+    #[test]
+    fn kana() {
+        assert_eq!(encode_char('ぁ'), Some([0xA4, 0xA1]));
+        assert_eq!(encode_char('ん'), Some([0xA4, 0xF3]));
+        assert_eq!(encode_char('ァ'), Some([0xA5, 0xA1]));
+        assert_eq!(encode_char('ヶ'), Some([0xA5, 0xF6]));
+    }
+
+    #[test]
+    fn greek() {
+        assert_eq!(encode_char('Α'), Some([0xA6, 0xA1]));
+        assert_eq!(encode_char('Ρ'), Some([0xA6, 0xB1]));
+        assert_eq!(encode_char('Σ'), Some([0xA6, 0xB2]));
+        assert_eq!(encode_char('Ω'), Some([0xA6, 0xB8]));
+        assert_eq!(encode_char('α'), Some([0xA6, 0xC1]));
+        assert_eq!(encode_char('ρ'), Some([0xA6, 0xD1]));
+        assert_eq!(encode_char('σ'), Some([0xA6, 0xD2]));
+        assert_eq!(encode_char('ω'), Some([0xA6, 0xD8]));
+        assert_eq!(encode_char('ς'), None);
+        assert_eq!(encode_char('\u{03A2}'), None);
+    }
+
+    #[test]
+    fn cyrillic() {
+        assert_eq!(encode_char('А'), Some([0xA7, 0xA1]));
+        assert_eq!(encode_char('Е'), Some([0xA7, 0xA6]));
+        assert_eq!(encode_char('Ё'), Some([0xA7, 0xA7]));
+        assert_eq!(encode_char('Ж'), Some([0xA7, 0xA8]));
+        assert_eq!(encode_char('Я'), Some([0xA7, 0xC1]));
+        assert_eq!(encode_char('а'), Some([0xA7, 0xD1]));
+        assert_eq!(encode_char('е'), Some([0xA7, 0xD6]));
+        assert_eq!(encode_char('ё'), Some([0xA7, 0xD7]));
+        assert_eq!(encode_char('ж'), Some([0xA7, 0xD8]));
+        assert_eq!(encode_char('я'), Some([0xA7, 0xF1]));
+    }
+
+    #[test]
+    fn box_drawing() {
+        assert_eq!(encode_char('─'), Some([0xA9, 0xA4]));
+        assert_eq!(encode_char('╋'), Some([0xA9, 0xEF]));
+        assert_eq!(encode_char('╌'), None);
+    }
+
+    #[test]
+    fn encode_mixed() {
+        let mut buf = [0; 16];
+        let len = encode::<(), ()>("Temp：25℃", &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"Temp\xA3\xBA25\xA1\xE6");
+    }
+
+    #[test]
+    fn encode_unencodable() {
+        let mut buf = [0; 16];
+        let result = encode::<(), ()>("ab中", &mut buf);
+        assert!(matches!(result, Err(Error::Unencodable('中'))));
+    }
+}
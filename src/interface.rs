@@ -0,0 +1,545 @@
+//! Connections to the controller.
+//!
+//! The controller is connected by SPI in its serial mode,
+//! or by an 8-bit or 4-bit parallel bus, see [`ParallelBus`].
+//! Any blocking [`SpiDevice`] can be used as an [`Interface`] directly.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{InputPin, OutputPin, PinState};
+use embedded_hal::spi::{self, SpiDevice};
+
+use crate::{Error, GDRAM_ROW_SIZE};
+
+/// Error type of an [`Interface`].
+///
+/// Implemented for all SPI devices, including async ones.
+pub trait ErrorType {
+    type Error;
+}
+
+impl<T: spi::ErrorType> ErrorType for T {
+    type Error = T::Error;
+}
+
+/// Error of an [`Interface`].
+#[derive(Debug)]
+pub enum InterfaceError<E> {
+    /// Error of the underlying bus
+    Bus(E),
+    /// The controller was still busy after the timeout
+    BusyTimeout,
+}
+
+impl<E> From<E> for InterfaceError<E> {
+    fn from(error: E) -> Self {
+        InterfaceError::Bus(error)
+    }
+}
+
+impl<CommError, PinError> From<InterfaceError<CommError>> for Error<CommError, PinError> {
+    fn from(error: InterfaceError<CommError>) -> Self {
+        match error {
+            InterfaceError::Bus(error) => Error::Comm(error),
+            InterfaceError::BusyTimeout => Error::BusyTimeout,
+        }
+    }
+}
+
+/// Connection to the controller.
+pub trait Interface: ErrorType {
+    /// Prepare the bus after a reset of the controller.
+    fn init(&mut self) -> Result<(), InterfaceError<Self::Error>> {
+        Ok(())
+    }
+
+    /// The interface waits until every instruction has been executed,
+    /// so the driver does not need to wait after long instructions.
+    fn waits_until_ready(&self) -> bool {
+        false
+    }
+
+    /// Send an instruction.
+    fn write_command(&mut self, command: u8) -> Result<(), InterfaceError<Self::Error>>;
+
+    /// Write data to DDRAM, CGRAM or GDRAM at the address counter.
+    fn write_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<Self::Error>>;
+
+    /// Set the vertical and horizontal GDRAM address and write data.
+    fn write_graphics(
+        &mut self,
+        address: [u8; 2],
+        data: &[u8],
+    ) -> Result<(), InterfaceError<Self::Error>> {
+        self.write_command(SET_GRAPHICS_ADDRESS | address[0])?;
+        self.write_command(SET_GRAPHICS_ADDRESS | address[1])?;
+        self.write_data(data)
+    }
+}
+
+const SET_GRAPHICS_ADDRESS: u8 = 0x80;
+
+/// Serial transfer of an instruction: sync byte followed by the two nibbles.
+pub(crate) fn command_bytes(command: u8) -> [u8; 3] {
+    [0xF8, command & 0xF0, (command << 4) & 0xF0]
+}
+
+/// Size of the serial transfer of a full GDRAM row, see [`encode_row`].
+pub(crate) const ROW_TRANSFER_SIZE: usize = 2 * 3 + 1 + 2 * GDRAM_ROW_SIZE;
+
+/// Encode setting the GDRAM address followed by writing `data`, as a single serial transfer.
+///
+/// The address counter increments after every word,
+/// so the data is sent as a continuous stream after a single sync byte.
+/// Returns the length of the transfer.
+pub(crate) fn encode_row(
+    transfer: &mut [u8; ROW_TRANSFER_SIZE],
+    address: [u8; 2],
+    data: &[u8],
+) -> usize {
+    transfer[0..3].copy_from_slice(&command_bytes(SET_GRAPHICS_ADDRESS | address[0]));
+    transfer[3..6].copy_from_slice(&command_bytes(SET_GRAPHICS_ADDRESS | address[1]));
+    6 + encode_data(&mut transfer[6..], data)
+}
+
+/// Encode writing `data` as a serial transfer, returning its length.
+pub(crate) fn encode_data(transfer: &mut [u8], data: &[u8]) -> usize {
+    transfer[0] = 0xFA;
+    let mut len = 1;
+    for d in data {
+        transfer[len] = d & 0xF0;
+        transfer[len + 1] = (d << 4) & 0xF0;
+        len += 2;
+    }
+    len
+}
+
+impl<SPI: SpiDevice> Interface for SPI {
+    fn write_command(&mut self, command: u8) -> Result<(), InterfaceError<Self::Error>> {
+        Ok(self.write(&command_bytes(command))?)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<Self::Error>> {
+        let mut transfer = [0; 1 + 2 * GDRAM_ROW_SIZE];
+        for chunk in data.chunks(GDRAM_ROW_SIZE) {
+            let len = encode_data(&mut transfer, chunk);
+            self.write(&transfer[..len])?;
+        }
+        Ok(())
+    }
+
+    fn write_graphics(
+        &mut self,
+        address: [u8; 2],
+        data: &[u8],
+    ) -> Result<(), InterfaceError<Self::Error>> {
+        let mut transfer = [0; ROW_TRANSFER_SIZE];
+        let len = encode_row(&mut transfer, address, data);
+        Ok(self.write(&transfer[..len])?)
+    }
+}
+
+/// Minimum width of the enable pulse.
+const ENABLE_PULSE_NS: u32 = 250;
+
+/// Execution time of most instructions and of data writes.
+const EXECUTION_TIME_US: u32 = 72;
+
+/// Function set instructions, selecting the bus width and instruction set.
+const FUNCTION_SET_MASK: u8 = 0xE0;
+const FUNCTION_SET: u8 = 0x20;
+const EIGHT_BIT: u8 = 0x10;
+
+/// Busy flag in the status read from the controller.
+const BUSY_FLAG: u8 = 0x80;
+
+/// [`ParallelBus`] waiting for the worst case execution time after every write.
+pub struct FixedDelay;
+
+/// [`ParallelBus`] polling the busy flag of the controller before every write.
+pub struct BusyFlag {
+    timeout_us: u32,
+}
+
+/// Parallel connection in 8-bit or 4-bit mode, with the PSB pin of the controller tied high.
+///
+/// In 4-bit mode only DB4 to DB7 are connected.
+/// By default the bus waits for the execution time of every instruction with `delay`,
+/// longer instructions like clearing the screen are waited for by the driver.
+/// See [`ParallelBus::with_busy_flag`] for polling the busy flag instead.
+pub struct ParallelBus<RS, RW, E, D, DELAY, const N: usize, P = FixedDelay> {
+    rs: RS,
+    rw: RW,
+    e: E,
+    data: [D; N],
+    delay: DELAY,
+    pacing: P,
+}
+
+impl<RS, RW, E, D, DELAY> ParallelBus<RS, RW, E, D, DELAY, 8> {
+    /// Create an 8-bit bus, `data` being DB0 to DB7
+    pub fn new_8bit(rs: RS, rw: RW, e: E, data: [D; 8], delay: DELAY) -> Self {
+        ParallelBus {
+            rs,
+            rw,
+            e,
+            data,
+            delay,
+            pacing: FixedDelay,
+        }
+    }
+}
+
+impl<RS, RW, E, D, DELAY> ParallelBus<RS, RW, E, D, DELAY, 4> {
+    /// Create a 4-bit bus, `data` being DB4 to DB7
+    pub fn new_4bit(rs: RS, rw: RW, e: E, data: [D; 4], delay: DELAY) -> Self {
+        ParallelBus {
+            rs,
+            rw,
+            e,
+            data,
+            delay,
+            pacing: FixedDelay,
+        }
+    }
+}
+
+impl<RS, RW, E, D, DELAY, const N: usize> ParallelBus<RS, RW, E, D, DELAY, N>
+where
+    D: InputPin + OutputPin,
+{
+    /// Poll the busy flag instead of waiting a fixed time after every write
+    ///
+    /// Requires the RW pin to be connected and the data pins to be readable while set high,
+    /// e.g. open-drain outputs with pull-up resistors.
+    /// Writes fail with [`Error::BusyTimeout`] if the controller is still busy after `timeout_us`.
+    pub fn with_busy_flag(self, timeout_us: u32) -> ParallelBus<RS, RW, E, D, DELAY, N, BusyFlag> {
+        ParallelBus {
+            rs: self.rs,
+            rw: self.rw,
+            e: self.e,
+            data: self.data,
+            delay: self.delay,
+            pacing: BusyFlag { timeout_us },
+        }
+    }
+}
+
+impl<RS, RW, E, D, DELAY, const N: usize, P> ErrorType for ParallelBus<RS, RW, E, D, DELAY, N, P>
+where
+    RS: OutputPin,
+{
+    type Error = RS::Error;
+}
+
+impl<RS, RW, E, D, DELAY, const N: usize, P> ParallelBus<RS, RW, E, D, DELAY, N, P>
+where
+    RS: OutputPin,
+    RW: OutputPin<Error = RS::Error>,
+    E: OutputPin<Error = RS::Error>,
+    D: OutputPin<Error = RS::Error>,
+    DELAY: DelayNs,
+{
+    /// Put the lowest `N` bits of `value` on the data pins and pulse enable.
+    fn write_bits(&mut self, value: u8) -> Result<(), RS::Error> {
+        for (i, pin) in self.data.iter_mut().enumerate() {
+            pin.set_state(PinState::from((value >> i) & 1 != 0))?;
+        }
+        self.e.set_high()?;
+        self.delay.delay_ns(ENABLE_PULSE_NS);
+        self.e.set_low()?;
+        self.delay.delay_ns(ENABLE_PULSE_NS);
+        Ok(())
+    }
+
+    fn write_byte(&mut self, rs: PinState, value: u8) -> Result<(), RS::Error> {
+        self.rs.set_state(rs)?;
+        self.rw.set_low()?;
+        if N == 4 {
+            self.write_bits(value >> 4)?;
+            self.write_bits(value & 0x0F)?;
+        } else {
+            self.write_bits(value)?;
+        }
+        Ok(())
+    }
+
+    fn init_bus(&mut self) -> Result<(), RS::Error> {
+        if N == 4 {
+            // Synchronize to 8-bit mode from any state, then switch to 4-bit mode.
+            // The busy flag can't be read before the bus width is known.
+            self.rs.set_low()?;
+            self.rw.set_low()?;
+            for nibble in [0x3, 0x3, 0x3, 0x2] {
+                self.write_bits(nibble)?;
+                self.delay.delay_us(EXECUTION_TIME_US);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Clear the 8-bit bus flag of function set instructions in 4-bit mode.
+fn bus_width_command<const N: usize>(command: u8) -> u8 {
+    if N == 4 && command & FUNCTION_SET_MASK == FUNCTION_SET {
+        command & !EIGHT_BIT
+    } else {
+        command
+    }
+}
+
+impl<RS, RW, E, D, DELAY, const N: usize> Interface
+    for ParallelBus<RS, RW, E, D, DELAY, N, FixedDelay>
+where
+    RS: OutputPin,
+    RW: OutputPin<Error = RS::Error>,
+    E: OutputPin<Error = RS::Error>,
+    D: OutputPin<Error = RS::Error>,
+    DELAY: DelayNs,
+{
+    fn init(&mut self) -> Result<(), InterfaceError<Self::Error>> {
+        Ok(self.init_bus()?)
+    }
+
+    fn write_command(&mut self, command: u8) -> Result<(), InterfaceError<Self::Error>> {
+        self.write_byte(PinState::Low, bus_width_command::<N>(command))?;
+        self.delay.delay_us(EXECUTION_TIME_US);
+        Ok(())
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<Self::Error>> {
+        for &d in data {
+            self.write_byte(PinState::High, d)?;
+            self.delay.delay_us(EXECUTION_TIME_US);
+        }
+        Ok(())
+    }
+}
+
+impl<RS, RW, E, D, DELAY, const N: usize> ParallelBus<RS, RW, E, D, DELAY, N, BusyFlag>
+where
+    RS: OutputPin,
+    RW: OutputPin<Error = RS::Error>,
+    E: OutputPin<Error = RS::Error>,
+    D: InputPin<Error = RS::Error> + OutputPin<Error = RS::Error>,
+    DELAY: DelayNs,
+{
+    /// Read the lowest `N` bits of the data pins while enable is high.
+    fn read_bits(&mut self) -> Result<u8, RS::Error> {
+        self.e.set_high()?;
+        self.delay.delay_ns(ENABLE_PULSE_NS);
+        let mut value = 0;
+        for (i, pin) in self.data.iter_mut().enumerate() {
+            if pin.is_high()? {
+                value |= 1 << i;
+            }
+        }
+        self.e.set_low()?;
+        self.delay.delay_ns(ENABLE_PULSE_NS);
+        Ok(value)
+    }
+
+    /// Read the busy flag and address counter.
+    fn read_status(&mut self) -> Result<u8, RS::Error> {
+        self.rs.set_low()?;
+        // Release the data pins, so the controller can drive them.
+        for pin in self.data.iter_mut() {
+            pin.set_high()?;
+        }
+        self.rw.set_high()?;
+        let status = if N == 4 {
+            (self.read_bits()? << 4) | self.read_bits()?
+        } else {
+            self.read_bits()?
+        };
+        self.rw.set_low()?;
+        Ok(status)
+    }
+
+    /// Poll the busy flag until the controller is ready or the timeout has passed.
+    fn wait_ready(&mut self) -> Result<(), InterfaceError<RS::Error>> {
+        let mut waited_us = 0;
+        while self.read_status()? & BUSY_FLAG != 0 {
+            if waited_us >= self.pacing.timeout_us {
+                return Err(InterfaceError::BusyTimeout);
+            }
+            self.delay.delay_us(1);
+            waited_us += 1;
+        }
+        Ok(())
+    }
+}
+
+impl<RS, RW, E, D, DELAY, const N: usize> Interface
+    for ParallelBus<RS, RW, E, D, DELAY, N, BusyFlag>
+where
+    RS: OutputPin,
+    RW: OutputPin<Error = RS::Error>,
+    E: OutputPin<Error = RS::Error>,
+    D: InputPin<Error = RS::Error> + OutputPin<Error = RS::Error>,
+    DELAY: DelayNs,
+{
+    fn init(&mut self) -> Result<(), InterfaceError<Self::Error>> {
+        Ok(self.init_bus()?)
+    }
+
+    fn waits_until_ready(&self) -> bool {
+        true
+    }
+
+    fn write_command(&mut self, command: u8) -> Result<(), InterfaceError<Self::Error>> {
+        self.wait_ready()?;
+        Ok(self.write_byte(PinState::Low, bus_width_command::<N>(command))?)
+    }
+
+    fn write_data(&mut self, data: &[u8]) -> Result<(), InterfaceError<Self::Error>> {
+        for &d in data {
+            self.wait_ready()?;
+            self.write_byte(PinState::High, d)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::convert::Infallible;
+
+    use embedded_hal::digital::ErrorType;
+
+    use super::*;
+
+    /// Controller answering status reads with the nibbles of `status`, high nibble first.
+    struct Controller {
+        status: [u8; 2],
+        enables: Cell<usize>,
+    }
+
+    enum Role {
+        Control,
+        Enable,
+        Data(u8),
+    }
+
+    struct Pin<'a> {
+        controller: &'a Controller,
+        role: Role,
+    }
+
+    impl ErrorType for Pin<'_> {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Pin<'_> {
+        fn set_low(&mut self) -> Result<(), Infallible> {
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Infallible> {
+            if let Role::Enable = self.role {
+                let enables = &self.controller.enables;
+                enables.set(enables.get() + 1);
+            }
+            Ok(())
+        }
+    }
+
+    impl InputPin for Pin<'_> {
+        fn is_high(&mut self) -> Result<bool, Infallible> {
+            let nibble = self.controller.status[(self.controller.enables.get() - 1) % 2];
+            Ok(match self.role {
+                Role::Data(bit) => (nibble >> bit) & 1 != 0,
+                _ => false,
+            })
+        }
+
+        fn is_low(&mut self) -> Result<bool, Infallible> {
+            Ok(!self.is_high()?)
+        }
+    }
+
+    /// Delay adding up the microseconds waited, ignoring the enable pulses.
+    struct Delay(u32);
+
+    impl DelayNs for Delay {
+        fn delay_ns(&mut self, _ns: u32) {}
+
+        fn delay_us(&mut self, us: u32) {
+            self.0 += us;
+        }
+    }
+
+    type Bus<'a> = ParallelBus<Pin<'a>, Pin<'a>, Pin<'a>, Pin<'a>, Delay, 4, BusyFlag>;
+
+    fn bus(controller: &Controller, timeout_us: u32) -> Bus<'_> {
+        let pin = |role| Pin { controller, role };
+        ParallelBus::new_4bit(
+            pin(Role::Control),
+            pin(Role::Control),
+            pin(Role::Enable),
+            [
+                pin(Role::Data(0)),
+                pin(Role::Data(1)),
+                pin(Role::Data(2)),
+                pin(Role::Data(3)),
+            ],
+            Delay(0),
+        )
+        .with_busy_flag(timeout_us)
+    }
+
+    #[test]
+    fn status_high_nibble_first() {
+        let controller = Controller {
+            status: [0x0B, 0x05],
+            enables: Cell::new(0),
+        };
+        assert_eq!(bus(&controller, 10).read_status().unwrap(), 0xB5);
+    }
+
+    #[test]
+    fn ready() {
+        let controller = Controller {
+            status: [0x07, 0x0F],
+            enables: Cell::new(0),
+        };
+        let mut bus = bus(&controller, 10);
+        assert!(bus.write_command(0x01).is_ok());
+        assert_eq!(bus.delay.0, 0);
+    }
+
+    #[test]
+    fn busy_timeout() {
+        let controller = Controller {
+            status: [0x08, 0x00],
+            enables: Cell::new(0),
+        };
+        let mut bus = bus(&controller, 10);
+        let error = bus.write_command(0x01).unwrap_err();
+        assert!(matches!(
+            Error::<Infallible, Infallible>::from(error),
+            Error::BusyTimeout
+        ));
+        assert_eq!(bus.delay.0, 10);
+    }
+
+    #[test]
+    fn row_transfer() {
+        let mut transfer = [0; ROW_TRANSFER_SIZE];
+        let len = encode_row(&mut transfer, [0x05, 0x08], &[0xAB, 0xCD]);
+        assert_eq!(len, 11);
+        assert_eq!(
+            transfer[..len],
+            [0xF8, 0x80, 0x50, 0xF8, 0x80, 0x80, 0xFA, 0xA0, 0xB0, 0xC0, 0xD0]
+        );
+    }
+
+    #[test]
+    fn full_row_transfer() {
+        let mut transfer = [0; ROW_TRANSFER_SIZE];
+        let len = encode_row(&mut transfer, [0, 0], &[0x12; GDRAM_ROW_SIZE]);
+        assert_eq!(len, ROW_TRANSFER_SIZE);
+        assert_eq!(transfer.iter().filter(|&&b| b == 0xFA).count(), 1);
+        assert!(transfer[7..].chunks(2).all(|pair| pair == [0x10, 0x20]));
+    }
+}
@@ -0,0 +1,165 @@
+//! Panel geometries.
+//!
+//! GDRAM rows are up to 256 pixels wide. Panels taller than 32 pixels continue
+//! each GDRAM row with the rows below, e.g. on a 128x64 panel the lower half
+//! is to the right of the upper half.
+
+use crate::text::TEXT_SIZE;
+use crate::GDRAM_ROW_SIZE;
+
+/// Geometry of the panel driven by the controller.
+///
+/// A layout the controller can't drive fails to build:
+///
+/// ```compile_fail
+/// use st7920::{NoPin, ST7920Builder};
+/// # struct Bus;
+/// # impl embedded_hal::spi::ErrorType for Bus {
+/// #     type Error = core::convert::Infallible;
+/// # }
+///
+/// struct Layout256x64;
+///
+/// impl st7920::Layout for Layout256x64 {
+///     const WIDTH: u32 = 256;
+///     const HEIGHT: u32 = 64;
+///     type Buffer = [u8; 2048];
+///     fn new_buffer() -> Self::Buffer {
+///         [0; 2048]
+///     }
+///     type Shadow = [u8; 0];
+///     fn new_shadow() -> Self::Shadow {
+///         []
+///     }
+/// }
+///
+/// let st7920 = ST7920Builder::<_, NoPin, NoPin>::new(Bus)
+///     .layout(Layout256x64)
+///     .build();
+/// ```
+pub trait Layout {
+    /// Width in pixels, a multiple of 16 and at most 256
+    const WIDTH: u32;
+
+    /// Height in pixels, 32 or 64
+    const HEIGHT: u32;
+
+    /// Storage for the buffer, `WIDTH * HEIGHT / 8` bytes
+    type Buffer: AsRef<[u8]> + AsMut<[u8]>;
+
+    /// Create a cleared buffer.
+    fn new_buffer() -> Self::Buffer;
+
+    /// Storage for the copy of the display memory used by the `shadow-buffer` feature,
+    /// empty without the feature
+    type Shadow: AsRef<[u8]> + AsMut<[u8]>;
+
+    /// Create the storage for the copy of the display memory.
+    fn new_shadow() -> Self::Shadow;
+}
+
+macro_rules! layout {
+    ($(#[$doc:meta])* $name:ident, $width:literal, $height:literal) => {
+        $(#[$doc])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        pub struct $name;
+
+        impl Layout for $name {
+            const WIDTH: u32 = $width;
+            const HEIGHT: u32 = $height;
+            type Buffer = [u8; $width * $height / 8];
+
+            fn new_buffer() -> Self::Buffer {
+                [0; $width * $height / 8]
+            }
+
+            #[cfg(feature = "shadow-buffer")]
+            type Shadow = [u8; $width * $height / 8];
+
+            #[cfg(feature = "shadow-buffer")]
+            fn new_shadow() -> Self::Shadow {
+                [0; $width * $height / 8]
+            }
+
+            #[cfg(not(feature = "shadow-buffer"))]
+            type Shadow = [u8; 0];
+
+            #[cfg(not(feature = "shadow-buffer"))]
+            fn new_shadow() -> Self::Shadow {
+                []
+            }
+        }
+    };
+}
+
+layout!(
+    /// 128x64 panel, the default
+    Layout128x64,
+    128,
+    64
+);
+layout!(
+    /// 256x32 panel
+    Layout256x32,
+    256,
+    32
+);
+layout!(
+    /// 192x32 panel
+    Layout192x32,
+    192,
+    32
+);
+layout!(
+    /// 128x32 panel
+    Layout128x32,
+    128,
+    32
+);
+
+/// Dimensions derived from a [`Layout`].
+pub(crate) trait Geometry: Layout {
+    /// Bytes in a buffer row
+    const ROW_SIZE: usize = Self::WIDTH as usize / 8;
+
+    /// Words in a buffer row
+    const ROW_WORDS: u8 = (Self::WIDTH / 16) as u8;
+
+    /// Number of GDRAM rows in use
+    const GDRAM_ROWS: u8 = if Self::HEIGHT < 32 {
+        Self::HEIGHT as u8
+    } else {
+        32
+    };
+
+    /// Words in a GDRAM row, covering one or more buffer rows
+    const GDRAM_ROW_WORDS: u8 = Self::ROW_WORDS * Self::HEIGHT.div_ceil(32) as u8;
+
+    /// Number of text lines of the built-in 8x16 font
+    const TEXT_LINES: u8 = (Self::HEIGHT / 16) as u8;
+
+    /// Number of half-width characters per text line
+    const TEXT_COLUMNS: u8 = (Self::WIDTH / 8) as u8;
+
+    /// Fails to build for a layout the controller can't drive, used by the driver constructors
+    const SUPPORTED: () = {
+        assert!(
+            Self::WIDTH % 16 == 0 && Self::WIDTH <= 256,
+            "the layout width must be a multiple of 16 and at most 256"
+        );
+        assert!(
+            Self::HEIGHT == 32 || Self::HEIGHT == 64,
+            "the layout height must be 32 or 64"
+        );
+        assert!(
+            Self::GDRAM_ROW_WORDS as usize * 2 <= GDRAM_ROW_SIZE,
+            "the layout must fit into the 256x32 pixels of GDRAM"
+        );
+        assert!(
+            Self::TEXT_LINES as usize * Self::TEXT_COLUMNS as usize <= TEXT_SIZE,
+            "the layout must fit into the 64 characters of DDRAM"
+        );
+    };
+}
+
+impl<L: Layout> Geometry for L {}
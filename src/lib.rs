@@ -2,102 +2,903 @@
 //!
 //! This is a Rust driver library for LCD displays using the [ST7920] controller.
 //!
-//! It supports graphics mode of the controller, 128x64 in 1bpp. SPI connection to MCU is supported.
+//! It supports graphics mode of the controller, 128x64 in 1bpp. SPI connection to MCU is supported,
+//! as well as 8-bit and 4-bit parallel connections, see [`interface`].
+//! Panels of 256x32, 192x32 and 128x32 pixels are supported as well, see [`Layout`].
+//!
+//! Text mode using the controller's built-in 8x16 font is available as well, see [`Mode::Text`].
+//! Both can be combined in [`Mode::Mixed`], where the text is shown on top of the graphics.
 //!
 //! The controller supports 1 bit-per-pixel displays, so an off-screen buffer has to be used to provide random access to pixels.
 //!
-//! Size of the buffer is 1024 bytes.
+//! Size of the buffer is 1024 bytes for a 128x64 panel.
+//! It is part of the driver by default, [`ST7920::new_with_buffer`] uses storage supplied by the caller instead.
+#![cfg_attr(
+    feature = "graphics",
+    doc = "Without any buffer, [`ST7920::draw_streamed`] renders a drawable into a small scratch buffer band by band, see [`Unbuffered`]."
+)]
 //!
 //! The buffer has to be flushed to update the display after a group of draw calls has been completed.
 //! The flush is not part of embedded-graphics API.
+//!
+//! An async driver using [embedded-hal-async] is available in the `asynch` module with the `async` feature.
+//!
+//! [embedded-hal-async]: https://docs.rs/embedded-hal-async
 
 #![no_std]
-use num_derive::ToPrimitive;
-use num_traits::ToPrimitive;
+use core::marker::PhantomData;
 
 use embedded_hal::delay::DelayNs;
-use embedded_hal::digital::OutputPin;
-use embedded_hal::spi::SpiDevice;
+use embedded_hal::digital::{self, OutputPin};
+
+use interface::Interface;
+use layout::Geometry;
+use sequence::{graphics_address_sequence, Step, GRAPHICS_SEQUENCE};
 
 #[derive(Debug)]
 pub enum Error<CommError, PinError> {
     Comm(CommError),
     Pin(PinError),
+    /// Character not supported by the character encoding
+    Unencodable(char),
+    /// The controller was still busy after the timeout, see [`ParallelBus::with_busy_flag`]
+    BusyTimeout,
 }
 
+#[cfg(feature = "async")]
+pub mod asynch;
+mod builder;
+pub mod gb2312;
+pub mod interface;
+mod layout;
+mod raster;
+mod scroll;
+mod sequence;
+mod shadow;
+#[cfg(feature = "graphics")]
+mod stream;
+mod text;
+mod timing;
+
+pub use builder::{NoPin, ST7920Builder};
+pub use interface::ParallelBus;
+pub use layout::{Layout, Layout128x32, Layout128x64, Layout192x32, Layout256x32};
+pub use raster::RasterOp;
+#[cfg(feature = "graphics")]
+pub use stream::Unbuffered;
+pub use text::CgramSlot;
+pub use timing::Timing;
+
 /// ST7920 instructions.
-#[derive(ToPrimitive)]
+///
+/// The basic and extended instruction sets reuse opcodes,
+/// so the opcode is resolved by [`Instruction::opcode`].
+#[derive(Clone, Copy)]
 enum Instruction {
-    BasicFunction = 0x30,
-    ExtendedFunction = 0x34,
-    ClearScreen = 0x01,
-    EntryMode = 0x06,
-    DisplayOnCursorOff = 0x0C,
-    GraphicsOn = 0x36,
-    SetGraphicsAddress = 0x80,
+    BasicFunction,
+    ExtendedFunction,
+    ClearScreen,
+    EntryMode,
+    DisplayOnCursorOff,
+    DisplayOff,
+    GraphicsOn,
+    Standby,
+    ScrollSelect,
+    SetScrollAddress,
+    Reverse,
+    SetGraphicsAddress,
+    SetDDRAMAddress,
+    SetCGRAMAddress,
+}
+
+impl Instruction {
+    fn opcode(self) -> u8 {
+        match self {
+            Instruction::BasicFunction => 0x30,
+            Instruction::ExtendedFunction => 0x34,
+            Instruction::ClearScreen => 0x01,
+            Instruction::EntryMode => 0x06,
+            Instruction::DisplayOnCursorOff => 0x0C,
+            Instruction::DisplayOff => 0x08,
+            Instruction::GraphicsOn => 0x36,
+            Instruction::Standby => 0x01,
+            Instruction::ScrollSelect => 0x02,
+            Instruction::SetScrollAddress => 0x40,
+            Instruction::Reverse => 0x04,
+            Instruction::SetGraphicsAddress => 0x80,
+            Instruction::SetDDRAMAddress => 0x80,
+            Instruction::SetCGRAMAddress => 0x40,
+        }
+    }
+}
+
+/// Display mode of the controller.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Bitmap from the off-screen buffer, updated with `flush`.
+    Graphics,
+    /// Lines of 8x16 characters using the built-in font, written directly to DDRAM.
+    ///
+    /// A 128x64 panel shows 4 lines of 16 characters.
+    ///
+    /// The graphics display is turned off and `flush` does nothing in this mode.
+    Text,
+    /// Text and graphics at the same time.
+    ///
+    /// The controller combines the text with the graphics by XOR,
+    /// so fixed labels can be kept as text while only the graphics is flushed.
+    Mixed,
+}
+
+impl Mode {
+    fn has_text(self) -> bool {
+        self != Mode::Graphics
+    }
+
+    fn has_graphics(self) -> bool {
+        self != Mode::Text
+    }
 }
 
-pub const WIDTH: u32 = 128;
-pub const HEIGHT: u32 = 64;
-const ROW_SIZE: usize = (WIDTH / 8) as usize;
-const BUFFER_SIZE: usize = ROW_SIZE * HEIGHT as usize;
+/// Width of the default [`Layout128x64`]
+pub const WIDTH: u32 = Layout128x64::WIDTH;
+/// Height of the default [`Layout128x64`]
+pub const HEIGHT: u32 = Layout128x64::HEIGHT;
 const X_ADDR_DIV: u8 = 16;
 
-pub struct ST7920<SPI, RST, CS>
-where
-    SPI: SpiDevice,
-    RST: OutputPin,
-    CS: OutputPin,
-{
-    /// SPI pin
-    spi: SPI,
+/// Size of a GDRAM row in bytes, 256 pixels.
+const GDRAM_ROW_SIZE: usize = 32;
+
+/// Rotation of the drawing on the display, clockwise.
+///
+/// Applies to graphics only, text is always shown upright.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rotation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
 
-    /// Reset pin.
-    rst: RST,
+/// Level of the chip select pin while the controller is selected.
+///
+/// The RS/CS pin of the controller is active high, level shifters may invert it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CsPolarity {
+    #[default]
+    ActiveHigh,
+    ActiveLow,
+}
+
+/// Rectangle of the buffer in whole GDRAM words, exclusive of right and bottom.
+///
+/// Uses buffer coordinates, i.e. after rotation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Window {
+    left: u8,
+    right: u8,
+    top: u8,
+    bottom: u8,
+}
+
+impl Window {
+    fn full<L: Layout>() -> Self {
+        Window {
+            left: 0,
+            right: L::ROW_WORDS,
+            top: 0,
+            bottom: L::HEIGHT as u8,
+        }
+    }
+
+    fn pixel(x: u8, y: u8) -> Self {
+        Window {
+            left: x / X_ADDR_DIV,
+            right: x / X_ADDR_DIV + 1,
+            top: y,
+            bottom: y + 1,
+        }
+    }
+
+    fn union(self, other: Window) -> Self {
+        Window {
+            left: self.left.min(other.left),
+            right: self.right.max(other.right),
+            top: self.top.min(other.top),
+            bottom: self.bottom.max(other.bottom),
+        }
+    }
+}
+
+/// Vertical and horizontal GDRAM address of a pixel.
+///
+/// Rows below the first 32 continue the GDRAM rows to the right,
+/// e.g. the lower half of a 128x64 display is to the right of the upper half.
+fn graphics_address<L: Layout>(x: u8, y: u8) -> [u8; 2] {
+    [y % 32, x / X_ADDR_DIV + (y / 32) * L::ROW_WORDS]
+}
+
+/// Size of the drawing in screen coordinates.
+fn screen_size<L: Layout>(rotation: Rotation) -> (u32, u32) {
+    match rotation {
+        Rotation::Deg0 | Rotation::Deg180 => (L::WIDTH, L::HEIGHT),
+        Rotation::Deg90 | Rotation::Deg270 => (L::HEIGHT, L::WIDTH),
+    }
+}
+
+/// Buffer rectangle as `(x, y, w, h)` of a region given in screen coordinates.
+///
+/// Mirroring is applied in screen coordinates, before the rotation.
+fn map_rect<L: Layout>(
+    rotation: Rotation,
+    mirror: (bool, bool),
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+) -> (u32, u32, u32, u32) {
+    let (mut x, mut y) = (x, y);
+    let (width, height) = screen_size::<L>(rotation);
+    if mirror.0 {
+        x = width - (x + w);
+    }
+    if mirror.1 {
+        y = height - (y + h);
+    }
+    match rotation {
+        Rotation::Deg0 => (x, y, w, h),
+        Rotation::Deg90 => (L::WIDTH - (y + h), x, h, w),
+        Rotation::Deg180 => (L::WIDTH - (x + w), L::HEIGHT - (y + h), w, h),
+        Rotation::Deg270 => (y, L::HEIGHT - (x + w), h, w),
+    }
+}
+
+/// Panic if a buffer does not fit the layout.
+fn check_buffer<L: Layout>(buffer: &[u8]) {
+    assert_eq!(
+        buffer.len(),
+        (L::WIDTH * L::HEIGHT / 8) as usize,
+        "buffer size does not match the layout"
+    );
+}
+
+pub struct ST7920<
+    DI,
+    RST,
+    CS,
+    L: Layout = Layout128x64,
+    B = <L as Layout>::Buffer,
+    S = <L as Layout>::Shadow,
+> {
+    /// Connection to the controller
+    interface: DI,
+
+    /// Reset pin, the controller is initialized by software without it.
+    rst: Option<RST>,
 
     /// CS pin
     cs: Option<CS>,
 
-    buffer: [u8; BUFFER_SIZE],
+    cs_polarity: CsPolarity,
+
+    buffer: B,
+
+    /// Buffer sent to the display when double buffering, [`ST7920::present`] swaps it with `buffer`.
+    front: Option<B>,
+
+    layout: PhantomData<L>,
+
+    rotation: Rotation,
+
+    /// Mirror the drawing horizontally
+    mirror_x: bool,
+
+    /// Mirror the drawing vertically
+    mirror_y: bool,
 
-    flip: bool,
+    mode: Mode,
+
+    timing: Timing,
+
+    /// Extended instruction set is selected.
+    extended: bool,
+
+    /// Controller is in standby, flushes are deferred until it is woken up.
+    asleep: bool,
+
+    display_on: bool,
+
+    /// Hardware scroll offset in GDRAM rows
+    scroll: u8,
+
+    scroll_enabled: bool,
+
+    /// Text line shown reversed, the instruction toggles the line.
+    reversed: Option<u8>,
+
+    /// Copy of the DDRAM text, needed to write at odd columns.
+    text: [u8; text::TEXT_SIZE],
+
+    /// Text cursor as (line, column)
+    cursor: (u8, u8),
+
+    /// Part of the buffer changed since the last flush
+    dirty: Option<Window>,
+
+    /// Buffer contents last sent to the display, empty if not used
+    shadow: S,
+
+    /// The shadow buffer matches the display memory
+    shadow_valid: bool,
 }
 
-impl<SPI, RST, CS, PinError, SPIError> ST7920<SPI, RST, CS>
+impl<DI, RST, CS, PinError, CommError> ST7920<DI, RST, CS>
 where
-    SPI: SpiDevice<Error = SPIError>,
-    RST: OutputPin<Error = PinError>,
-    CS: OutputPin<Error = PinError>,
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+{
+    /// Create a new [`ST7920<DI, RST, CS>`] driver instance that uses an SPI or parallel connection.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use st7920::ST7920;
+    ///
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+    /// let mut st7920 = ST7920::new(spi, reset, Some(cs), false);
+    /// st7920.init(&mut delay)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(interface: DI, rst: RST, cs: Option<CS>, flip: bool) -> Self {
+        Self::new_with_layout(interface, rst, cs, flip, Layout128x64)
+    }
+}
+
+impl<DI, RST, PinError, CommError> ST7920<DI, RST, NoPin<PinError>>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    PinError: digital::Error,
 {
-    /// Create a new [`ST7920<SPI, RST, CS>`] driver instance that uses SPI connection.
+    /// Create a new driver instance without a CS pin,
+    /// e.g. when the CS is handled by the SPI device or tied high.
     ///
     /// # Examples
     ///
     /// ```no_run
     /// use st7920::ST7920;
     ///
-    /// let result = ST7920::new(spi, GPIO::new(pins.p01), None, false);
-    /// assert_eq!(result, );
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+    /// let mut st7920 = ST7920::new_without_cs(spi, reset, false);
+    /// st7920.init(&mut delay)?;
+    /// # Ok(())
+    /// # }
     /// ```
-    pub fn new(spi: SPI, rst: RST, cs: Option<CS>, flip: bool) -> Self {
-        let buffer = [0; BUFFER_SIZE];
+    pub fn new_without_cs(interface: DI, rst: RST, flip: bool) -> Self {
+        Self::new(interface, rst, None, flip)
+    }
+}
+
+impl<DI, RST, CS, L, PinError, CommError> ST7920<DI, RST, CS, L>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+{
+    /// Create a new driver instance for a panel with the given [`Layout`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use st7920::{Layout256x32, ST7920};
+    ///
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+    /// let mut st7920 = ST7920::new_with_layout(spi, reset, Some(cs), false, Layout256x32);
+    /// st7920.init(&mut delay)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_layout(interface: DI, rst: RST, cs: Option<CS>, flip: bool, layout: L) -> Self {
+        Self::new_with_buffer(interface, rst, cs, flip, layout, L::new_buffer())
+    }
+}
+
+impl<DI, RST, CS, L, B, PinError, CommError> ST7920<DI, RST, CS, L, B>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Create a new driver instance drawing to the given buffer, e.g. in a `static`
+    /// or a specific RAM section.
+    ///
+    /// The buffer contents are kept, so it can hold e.g. a splash screen.
+    ///
+    /// # Panics
+    ///
+    /// If the buffer is not `WIDTH * HEIGHT / 8` bytes long for the [`Layout`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use st7920::{Layout128x64, ST7920};
+    ///
+    /// static mut BUFFER: [u8; 1024] = [0; 1024];
+    ///
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+    /// let buffer = unsafe { &mut *core::ptr::addr_of_mut!(BUFFER) };
+    /// let mut st7920 = ST7920::new_with_buffer(spi, reset, Some(cs), false, Layout128x64, buffer);
+    /// st7920.init(&mut delay)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_buffer(
+        interface: DI,
+        rst: RST,
+        cs: Option<CS>,
+        flip: bool,
+        _layout: L,
+        buffer: B,
+    ) -> Self {
+        let rotation = if flip {
+            Rotation::Deg180
+        } else {
+            Rotation::Deg0
+        };
+        check_buffer::<L>(buffer.as_ref());
+        Self::from_parts(interface, Some(rst), cs, rotation, buffer, L::new_shadow())
+    }
+}
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Forget the state of the controller, e.g. after a reset.
+    fn reset_state(&mut self) {
+        self.invalidate_shadow();
+        self.reset_text();
+        self.extended = false;
+        self.asleep = false;
+        self.display_on = true;
+        self.scroll = 0;
+        self.scroll_enabled = false;
+        self.reversed = None;
+    }
 
+    fn from_parts(
+        interface: DI,
+        rst: Option<RST>,
+        cs: Option<CS>,
+        rotation: Rotation,
+        buffer: B,
+        shadow: S,
+    ) -> Self {
+        let () = L::SUPPORTED;
         ST7920 {
-            spi,
+            interface,
             rst,
             cs,
+            cs_polarity: CsPolarity::ActiveHigh,
             buffer,
-            flip,
+            front: None,
+            layout: PhantomData,
+            rotation,
+            mirror_x: false,
+            mirror_y: false,
+            mode: Mode::Graphics,
+            timing: Timing::default(),
+            extended: false,
+            asleep: false,
+            display_on: true,
+            scroll: 0,
+            scroll_enabled: false,
+            reversed: None,
+            text: [b' '; text::TEXT_SIZE],
+            cursor: (0, 0),
+            dirty: None,
+            shadow,
+            shadow_valid: false,
+        }
+    }
+
+    /// Use a different [`Timing`] profile, e.g. for slower clones of the controller
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    /// Use a different [`CsPolarity`], e.g. behind an inverting level shifter
+    pub fn with_cs_polarity(mut self, polarity: CsPolarity) -> Self {
+        self.cs_polarity = polarity;
+        self
+    }
+
+    /// Initialize the controller in the given [`Mode`] instead of [`Mode::Graphics`]
+    ///
+    /// A display that is only used in [`Mode::Text`] is set up without turning the graphics on.
+    /// Use [`set_mode`](Self::set_mode) to switch the mode of an initialized display.
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Get the level of the CS pin while the controller is selected
+    pub fn cs_polarity(&self) -> CsPolarity {
+        self.cs_polarity
+    }
+
+    /// Destroy the driver and return the interface, reset pin and CS pin
+    ///
+    /// The pins are `None` if the driver was created without them.
+    pub fn release(self) -> (DI, Option<RST>, Option<CS>) {
+        (self.interface, self.rst, self.cs)
+    }
+
+    /// Destroy the driver and return the interface, pins and buffer
+    ///
+    /// The buffer is in the layout of the display memory, unaffected by rotation and mirroring.
+    ///
+    /// With double buffering, the front buffer is dropped, see [`disable_double_buffer`](Self::disable_double_buffer).
+    pub fn free(self) -> (DI, Option<RST>, Option<CS>, B) {
+        (self.interface, self.rst, self.cs, self.buffer)
+    }
+
+    /// Modify the raw buffer. 1 byte (u8) = 8 pixels
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+    /// let mut st7920 = st7920::ST7920::new(spi, reset, Some(cs), false);
+    /// // add crazy pattern and count the pixels that are set
+    /// let mut pixels = 0;
+    /// st7920.modify_buffer(|x, y, v| {
+    ///     let v = if x % 2 == y % 2 { v | 0b10101010 } else { v };
+    ///     pixels += v.count_ones();
+    ///     v
+    /// });
+    /// st7920.flush(&mut delay)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn modify_buffer<F>(&mut self, mut f: F)
+    where
+        F: FnMut(u8, u8, u8) -> u8,
+    {
+        for (i, v) in self.buffer.as_mut().iter_mut().enumerate() {
+            let row = i / L::ROW_SIZE;
+            let column = i - (row * L::ROW_SIZE);
+            *v = f(column as u8, row as u8, *v);
+        }
+        self.mark_dirty(Window::full::<L>());
+    }
+
+    /// Draw to a back buffer and flush the given front buffer until [`present`](Self::present)
+    ///
+    /// Keeps a flush, e.g. from an interrupt, from sending a half drawn frame.
+    /// The front buffer takes the contents of the buffer.
+    ///
+    /// # Panics
+    ///
+    /// If the buffer is not `WIDTH * HEIGHT / 8` bytes long for the [`Layout`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+    /// let mut st7920 = st7920::ST7920::new(spi, reset, Some(cs), false);
+    /// st7920.enable_double_buffer([0; 1024]);
+    /// // draw the next frame
+    /// st7920.present();
+    /// st7920.flush(&mut delay)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn enable_double_buffer(&mut self, mut front: B) {
+        check_buffer::<L>(front.as_ref());
+        front.as_mut().copy_from_slice(self.buffer.as_ref());
+        self.front = Some(front);
+    }
+
+    /// Stop double buffering and return the front buffer
+    ///
+    /// Flushes send the drawing buffer again.
+    pub fn disable_double_buffer(&mut self) -> Option<B> {
+        let front = self.front.take();
+        if front.is_some() {
+            self.mark_dirty(Window::full::<L>());
+        }
+        front
+    }
+
+    /// Swap the back buffer drawn to with the front buffer sent by flushes
+    ///
+    /// Drawing continues on the previous front buffer, so the next frame has to be drawn completely.
+    /// Does nothing without double buffering.
+    pub fn present(&mut self) {
+        if let Some(front) = self.front.as_mut() {
+            core::mem::swap(front, &mut self.buffer);
+            self.mark_dirty(Window::full::<L>());
+        }
+    }
+
+    /// Buffer sent to the display
+    pub(crate) fn front(&self) -> &[u8] {
+        self.front.as_ref().unwrap_or(&self.buffer).as_ref()
+    }
+
+    /// The buffer drawn to, 1 byte (u8) = 8 pixels
+    ///
+    /// The buffer is in the layout of the display memory, unaffected by rotation and mirroring.
+    pub fn buffer(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+
+    /// Mutable access to the buffer drawn to, see [`buffer`](Self::buffer)
+    ///
+    /// The whole buffer is sent by the next [`flush_dirty`](Self::flush_dirty).
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
+        self.mark_dirty(Window::full::<L>());
+        self.buffer.as_mut()
+    }
+
+    /// clears the buffer but don't update the display
+    pub fn clear_buffer(&mut self) {
+        self.buffer.as_mut().fill(0);
+        self.mark_dirty(Window::full::<L>());
+    }
+
+    /// Clear a buffer region.
+    ///
+    /// If the region is completely off screen,
+    /// nothing will be done and Ok()) will be returned.
+    /// If the given width or height are too big,
+    /// width and height will be trimmed to the screen dimensions.
+    pub fn clear_buffer_region(
+        &mut self,
+        x: u8,
+        y: u8,
+        w: u8,
+        h: u8,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.apply_region(x, y, w, h, RasterOp::AndNot);
+        Ok(())
+    }
+
+    /// Draw pixel
+    ///
+    /// Doesn't draw anything, if the x or y coordinates are off canvas.
+    ///
+    /// Supported values are 0 and (not 0)
+    #[inline]
+    pub fn set_pixel(&mut self, x: u8, y: u8, val: u8) {
+        let (width, height) = self.dimensions();
+        if (x as u32) < width && (y as u32) < height && !self.buffer.as_ref().is_empty() {
+            self.set_pixel_unchecked(x, y, val);
         }
     }
 
+    /// Draw pixel without canvas bounds checking.
+    ///
+    /// Supported values are 0 and (not 0)
+    ///
+    /// # Panics
+    ///
+    /// May panic or draw to undefined pixels, if x or y coordinates are off canvas.
+    #[inline]
+    pub fn set_pixel_unchecked(&mut self, x: u8, y: u8, val: u8) {
+        let (x, y, _, _) = self.buffer_rect(x, y, 1, 1);
+        let (x, y) = (x as u8, y as u8);
+        let idx = y as usize * L::ROW_SIZE + x as usize / 8;
+        let x_mask = 0x80 >> (x % 8);
+        if val != 0 {
+            self.buffer.as_mut()[idx] |= x_mask;
+        } else {
+            self.buffer.as_mut()[idx] &= !x_mask;
+        }
+        self.mark_dirty(Window::pixel(x, y));
+    }
+
+    /// Current display mode
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The controller is in standby, see [`ST7920::sleep`]
+    pub fn is_asleep(&self) -> bool {
+        self.asleep
+    }
+
+    /// The display is turned on, see [`ST7920::display_on`]
+    pub fn is_display_on(&self) -> bool {
+        self.display_on
+    }
+
+    /// Buffer contents can be sent to the display
+    pub(crate) fn can_flush(&self) -> bool {
+        self.shows_graphics() && !self.front().is_empty()
+    }
+
+    /// Graphics can be sent to the display
+    pub(crate) fn shows_graphics(&self) -> bool {
+        self.mode.has_graphics() && !self.asleep
+    }
+
+    /// Current rotation of the drawing
+    pub fn rotation(&self) -> Rotation {
+        self.rotation
+    }
+
+    /// Rotate the drawing
+    ///
+    /// Only affects drawing after the change, the buffer contents are kept as they are.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+    }
+
+    /// Horizontal and vertical mirroring of the drawing
+    pub fn mirror(&self) -> (bool, bool) {
+        (self.mirror_x, self.mirror_y)
+    }
+
+    /// Mirror the drawing horizontally and/or vertically
+    ///
+    /// Mirroring is applied to the drawing before rotating it, so `mirror_x` always
+    /// swaps left and right as seen by the viewer.
+    /// Only affects drawing after the change, the buffer contents are kept as they are.
+    pub fn set_mirror(&mut self, mirror_x: bool, mirror_y: bool) {
+        self.mirror_x = mirror_x;
+        self.mirror_y = mirror_y;
+    }
+
+    /// Width and height of the drawing, swapped when rotated by 90 or 270 degrees.
+    fn dimensions(&self) -> (u32, u32) {
+        screen_size::<L>(self.rotation)
+    }
+
+    /// Limit a region to the screen, returning the trimmed width and height.
+    ///
+    /// Returns `None` if the region is completely off screen or empty.
+    fn clip_region(&self, x: u8, y: u8, mut w: u8, mut h: u8) -> Option<(u8, u8)> {
+        let (width, height) = self.dimensions();
+        // Top-left is on screen and region has a width/height?
+        if (x as u32) < width && (y as u32) < height && w > 0 && h > 0 {
+            // Limit width and height to right and bottom edge.
+            if x as u32 + w as u32 > width {
+                w = (width - x as u32) as u8;
+            }
+            if y as u32 + h as u32 > height {
+                h = (height - y as u32) as u8;
+            }
+            Some((w, h))
+        } else {
+            None
+        }
+    }
+
+    /// Buffer rectangle as `(x, y, w, h)` of a region given in screen coordinates.
+    fn buffer_rect(&self, x: u8, y: u8, w: u8, h: u8) -> (u32, u32, u32, u32) {
+        map_rect::<L>(
+            self.rotation,
+            self.mirror(),
+            x as u32,
+            y as u32,
+            w as u32,
+            h as u32,
+        )
+    }
+
+    /// Text lines and half-width characters per line of the built-in font
+    pub fn text_size(&self) -> (u8, u8) {
+        (L::TEXT_LINES, L::TEXT_COLUMNS)
+    }
+
+    /// Copy words `start..end` of the `y`th GDRAM row to `row`,
+    /// returning their GDRAM address and length in bytes.
+    ///
+    /// On panels taller than 32 pixels, a GDRAM row continues with the rows below.
+    fn gdram_words(
+        &self,
+        row: &mut [u8; GDRAM_ROW_SIZE],
+        y: u8,
+        start: u8,
+        end: u8,
+    ) -> ([u8; 2], usize) {
+        let buffer = self.front();
+        for (word, data) in (start..end).zip(row.chunks_exact_mut(2)) {
+            let row = y as usize + (word / L::ROW_WORDS) as usize * 32;
+            let i = row * L::ROW_SIZE + (word % L::ROW_WORDS) as usize * 2;
+            data.copy_from_slice(&buffer[i..i + 2]);
+        }
+        let x = (start % L::ROW_WORDS) * X_ADDR_DIV;
+        let y = y + (start / L::ROW_WORDS) * 32;
+        (graphics_address::<L>(x, y), (end - start) as usize * 2)
+    }
+
+    /// Buffer window covering a region given in screen coordinates.
+    fn window(&self, x: u8, y: u8, w: u8, h: u8) -> Window {
+        let (left, top, w, h) = self.buffer_rect(x, y, w, h);
+
+        Window {
+            left: (left / X_ADDR_DIV as u32) as u8,
+            right: ((left + w - 1) / X_ADDR_DIV as u32 + 1) as u8,
+            top: top as u8,
+            bottom: (top + h) as u8,
+        }
+    }
+
+    /// Add a window to the dirty region.
+    #[inline]
+    fn mark_dirty(&mut self, window: Window) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(window),
+            None => window,
+        });
+    }
+
+    /// Copy the `y`th buffer row of a window to `row`,
+    /// returning its GDRAM address and length in bytes.
+    fn window_row(
+        &self,
+        row: &mut [u8; GDRAM_ROW_SIZE],
+        window: Window,
+        y: u8,
+    ) -> ([u8; 2], usize) {
+        let row_start = y as usize * L::ROW_SIZE;
+        let data = &self.front()
+            [row_start + window.left as usize * 2..row_start + window.right as usize * 2];
+        row[..data.len()].copy_from_slice(data);
+        (
+            graphics_address::<L>(window.left * X_ADDR_DIV, y),
+            data.len(),
+        )
+    }
+}
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: Interface<Error = CommError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
     fn enable_cs<Delay: DelayNs>(
         &mut self,
         delay: &mut Delay,
-    ) -> Result<(), Error<SPIError, PinError>> {
+    ) -> Result<(), Error<CommError, PinError>> {
         if let Some(cs) = self.cs.as_mut() {
-            cs.set_high().map_err(Error::Pin)?;
-            delay.delay_us(1);
+            match self.cs_polarity {
+                CsPolarity::ActiveHigh => cs.set_high(),
+                CsPolarity::ActiveLow => cs.set_low(),
+            }
+            .map_err(Error::Pin)?;
+            delay.delay_us(self.timing.cs_us);
         }
         Ok(())
     }
@@ -105,10 +906,14 @@ where
     fn disable_cs<Delay: DelayNs>(
         &mut self,
         delay: &mut Delay,
-    ) -> Result<(), Error<SPIError, PinError>> {
+    ) -> Result<(), Error<CommError, PinError>> {
         if let Some(cs) = self.cs.as_mut() {
-            delay.delay_us(1);
-            cs.set_low().map_err(Error::Pin)?;
+            delay.delay_us(self.timing.cs_us);
+            match self.cs_polarity {
+                CsPolarity::ActiveHigh => cs.set_low(),
+                CsPolarity::ActiveLow => cs.set_high(),
+            }
+            .map_err(Error::Pin)?;
         }
         Ok(())
     }
@@ -117,288 +922,384 @@ where
     fn do_init<Delay: DelayNs>(
         &mut self,
         delay: &mut Delay,
-    ) -> Result<(), Error<SPIError, PinError>> {
+    ) -> Result<(), Error<CommError, PinError>> {
         self.hard_reset(delay)?;
-        self.write_command(Instruction::BasicFunction)?;
-        delay.delay_us(200);
-        self.write_command(Instruction::DisplayOnCursorOff)?;
-        delay.delay_us(100);
-        self.write_command(Instruction::ClearScreen)?;
-        delay.delay_ms(10);
-        self.write_command(Instruction::EntryMode)?;
-        delay.delay_us(100);
-        self.write_command(Instruction::ExtendedFunction)?;
-        delay.delay_ms(10);
-        self.write_command(Instruction::GraphicsOn)?;
-        delay.delay_ms(100);
-        Ok(())
+        self.reset_state();
+        self.run(&self.init_sequence(), delay)
     }
 
     /// Initialize the display controller
+    ///
+    /// The controller is set up in the current [`Mode`], which is [`Mode::Graphics`] by default,
+    /// see [`with_mode`](Self::with_mode).
     pub fn init<Delay: DelayNs>(
         &mut self,
         delay: &mut Delay,
-    ) -> Result<(), Error<SPIError, PinError>> {
+    ) -> Result<(), Error<CommError, PinError>> {
         self.enable_cs(delay)?;
         let result = self.do_init(delay);
         self.disable_cs(delay)?;
         result
     }
 
-    fn hard_reset<Delay: DelayNs>(
+    #[inline]
+    fn do_set_mode<Delay: DelayNs>(
         &mut self,
+        mode: Mode,
         delay: &mut Delay,
-    ) -> Result<(), Error<SPIError, PinError>> {
-        self.rst.set_low().map_err(Error::Pin)?;
-        delay.delay_ms(40);
-        self.rst.set_high().map_err(Error::Pin)?;
-        delay.delay_ms(40);
+    ) -> Result<(), Error<CommError, PinError>> {
+        match mode {
+            Mode::Graphics => {
+                // Text would be combined with the graphics, so clear it first.
+                self.select_basic()?;
+                self.wait_us(delay, self.timing.command_us);
+                self.write_command(Instruction::ClearScreen)?;
+                self.wait_us(delay, self.timing.clear_us);
+                self.reset_text();
+                self.select_extended(delay)?;
+                self.wait_us(delay, self.timing.command_us);
+                self.write_command(Instruction::GraphicsOn)?;
+                self.wait_us(delay, self.timing.command_us);
+            }
+            Mode::Text => {
+                // Graphics display off, then back to the basic instruction set.
+                self.select_extended(delay)?;
+                self.wait_us(delay, self.timing.command_us);
+                self.write_command(Instruction::ExtendedFunction)?;
+                self.wait_us(delay, self.timing.command_us);
+                self.select_basic()?;
+                self.wait_us(delay, self.timing.command_us);
+                self.sync_cursor()?;
+            }
+            Mode::Mixed => {
+                self.select_extended(delay)?;
+                self.wait_us(delay, self.timing.command_us);
+                self.write_command(Instruction::GraphicsOn)?;
+                self.wait_us(delay, self.timing.command_us);
+            }
+        }
+        self.mode = mode;
         Ok(())
     }
 
-    fn write_command(&mut self, command: Instruction) -> Result<(), Error<SPIError, PinError>> {
-        self.write_command_param(command, 0)
+    /// Switch the display mode of an initialized display.
+    ///
+    /// Switching to [`Mode::Graphics`] clears the text, switching to [`Mode::Mixed`] keeps it.
+    /// The graphics buffer is kept, but has to be flushed again after switching from [`Mode::Text`].
+    pub fn set_mode<Delay: DelayNs>(
+        &mut self,
+        mode: Mode,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if mode == self.mode {
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self.do_set_mode(mode, delay);
+        self.disable_cs(delay)?;
+        result
     }
 
-    fn write_command_param(
+    /// Put the controller into standby to save power
+    ///
+    /// The display is blank and keeps its contents. Drawing to the buffer is still allowed,
+    /// flushes are deferred until [`wake`](Self::wake). Any text mode function wakes
+    /// the controller up as well.
+    pub fn sleep<Delay: DelayNs>(
         &mut self,
-        command: Instruction,
-        param: u8,
-    ) -> Result<(), Error<SPIError, PinError>> {
-        let command_param = command.to_u8().unwrap() | param;
-        let cmd: u8 = 0xF8;
-
-        self.spi
-            .write(&[cmd, command_param & 0xF0, (command_param << 4) & 0xF0])
-            .map_err(Error::Comm)?;
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if self.asleep {
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self.do_sleep(delay);
+        self.disable_cs(delay)?;
+        result
+    }
 
+    fn do_sleep<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.run(&self.sleep_sequence(), delay)?;
+        self.asleep = true;
         Ok(())
     }
 
-    fn write_data(&mut self, data: u8) -> Result<(), Error<SPIError, PinError>> {
-        self.spi
-            .write(&[0xFA, data & 0xF0, (data << 4) & 0xF0])
-            .map_err(Error::Comm)?;
-        Ok(())
+    /// Wake the controller up from standby
+    ///
+    /// Restores the display state and the mode. With `flush`, the whole buffer is sent,
+    /// otherwise only the parts drawn while asleep.
+    pub fn wake<Delay: DelayNs>(
+        &mut self,
+        flush: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.asleep {
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self.do_wake(delay);
+        self.disable_cs(delay)?;
+        result?;
+        if flush {
+            self.invalidate_shadow();
+            self.flush(delay)
+        } else {
+            self.flush_dirty(delay)
+        }
     }
 
-    fn set_address(&mut self, x: u8, y: u8) -> Result<(), Error<SPIError, PinError>> {
-        const HALF_HEIGHT: u8 = HEIGHT as u8 / 2;
+    fn do_wake<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.run(&self.wake_sequence(), delay)
+    }
 
-        self.write_command_param(
-            Instruction::SetGraphicsAddress,
-            if y < HALF_HEIGHT { y } else { y - HALF_HEIGHT },
-        )?;
-        self.write_command_param(
-            Instruction::SetGraphicsAddress,
-            if y < HALF_HEIGHT {
-                x / X_ADDR_DIV
-            } else {
-                x / X_ADDR_DIV + (WIDTH as u8 / X_ADDR_DIV)
-            },
-        )?;
+    /// Turn the display on or off
+    ///
+    /// The display memory is kept and can still be written while the display is off.
+    pub fn display_on<Delay: DelayNs>(
+        &mut self,
+        on: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.enable_cs(delay)?;
+        let result = self.do_display_on(on, delay);
+        self.disable_cs(delay)?;
+        result
+    }
 
+    fn do_display_on<Delay: DelayNs>(
+        &mut self,
+        on: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.run(&self.display_on_sequence(on), delay)?;
+        self.display_on = on;
         Ok(())
     }
 
-    /// Modify the raw buffer. 1 byte (u8) = 8 pixels
-    ///
-    /// # Examples
+    /// Select the basic instruction set, used for text.
     ///
-    /// ```no_run
-    /// let mut st7920 = st7920::ST7920(...);
-    /// // add crazy pattern
-    /// st7920.modify_buffer(|x, y, v| {
-    ///     if x % 2 == y % 2 {
-    ///         v | 0b10101010
-    ///     } else {
-    ///         v
-    ///     }
-    /// });
-    /// st7920.flush();
-    /// ```
-    pub fn modify_buffer(&mut self, f: fn(x: u8, y: u8, v: u8) -> u8) {
-        for i in 0..BUFFER_SIZE {
-            let row = i / ROW_SIZE;
-            let column = i - (row * ROW_SIZE);
-            self.buffer[i] = f(column as u8, row as u8, self.buffer[i]);
-        }
-    }
-
-    /// clears the buffer but don't update the display
-    pub fn clear_buffer(&mut self) {
-        for i in 0..BUFFER_SIZE {
-            self.buffer[i] = 0;
+    /// Changing the instruction set leaves the graphics display on or off.
+    fn select_basic(&mut self) -> Result<(), Error<CommError, PinError>> {
+        if self.extended {
+            self.write_command(Instruction::BasicFunction)?;
         }
+        Ok(())
     }
 
-    /// Clear whole display area and clears the buffer
-    pub fn clear<Delay: DelayNs>(
+    /// Select the extended instruction set, used for graphics.
+    ///
+    /// The extended function set clears the graphics display bit, so it is set again
+    /// by a second instruction if the mode shows graphics.
+    fn select_extended<Delay: DelayNs>(
         &mut self,
         delay: &mut Delay,
-    ) -> Result<(), Error<SPIError, PinError>> {
-        self.clear_buffer();
-        self.flush(delay)?;
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.extended {
+            self.write_command(Instruction::ExtendedFunction)?;
+            if self.mode.has_graphics() {
+                self.wait_us(delay, self.timing.command_us);
+                self.write_command(Instruction::GraphicsOn)?;
+            }
+        }
         Ok(())
     }
 
-    /// Clear a buffer region.
-    ///
-    /// If the region is completely off screen,
-    /// nothing will be done and Ok()) will be returned.
-    /// If the given width or height are too big,
-    /// width and height will be trimmed to the screen dimensions.
-    pub fn clear_buffer_region(
+    fn hard_reset<Delay: DelayNs>(
         &mut self,
-        x: u8,
-        mut y: u8,
-        mut w: u8,
-        mut h: u8,
-    ) -> Result<(), Error<SPIError, PinError>> {
-        // Top-left is on screen and region has a width/height?
-        if x < WIDTH as u8 && y < HEIGHT as u8 && w > 0 && h > 0 {
-            // Limit width and height to right and bottom edge.
-            if x.saturating_add(w) > WIDTH as u8 {
-                w = WIDTH as u8 - x;
-            }
-            if y.saturating_add(h) > HEIGHT as u8 {
-                h = HEIGHT as u8 - y;
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        match self.rst.as_mut() {
+            Some(rst) => {
+                rst.set_low().map_err(Error::Pin)?;
+                delay.delay_ms(self.timing.reset_ms);
+                rst.set_high().map_err(Error::Pin)?;
+                delay.delay_ms(self.timing.reset_ms);
+                self.interface.init()?;
             }
-
-            let mut adj_x = x;
-            if self.flip {
-                y = HEIGHT as u8 - (y + h);
-                adj_x = WIDTH as u8 - (x + w);
+            None => {
+                // Wait for the power-on reset.
+                delay.delay_ms(self.timing.reset_ms);
+                self.interface.init()?;
+                self.run(&self.soft_reset_sequence(), delay)?;
             }
+        }
+        Ok(())
+    }
 
-            let start = adj_x / 8;
-            let mut right = adj_x + w;
-            let end = (right / 8) + 1;
-
-            let start_gap = adj_x % 8;
-
-            right = end * 8;
-
-            let end_gap = 8 - (right % 8);
+    /// Wait for an instruction to complete, unless the interface waits by itself.
+    fn wait_us<Delay: DelayNs>(&self, delay: &mut Delay, us: u32) {
+        if !self.interface.waits_until_ready() {
+            delay.delay_us(us);
+        }
+    }
 
-            let mut row_start = y as usize * ROW_SIZE;
-            for _ in y..y + h {
-                for x in start..end {
-                    let mut mask = 0xFF_u8;
-                    if x == start {
-                        mask = 0xFF_u8 >> start_gap;
-                    }
-                    if x == end {
-                        mask &= 0xFF_u8 >> end_gap;
-                    }
+    fn write_command(&mut self, command: Instruction) -> Result<(), Error<CommError, PinError>> {
+        self.write_command_param(command, 0)
+    }
 
-                    let pos = row_start + x as usize;
-                    self.buffer[pos] &= !mask;
-                }
+    fn write_command_param(
+        &mut self,
+        command: Instruction,
+        param: u8,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.interface.write_command(command.opcode() | param)?;
+        self.command_sent(command);
+        Ok(())
+    }
 
-                row_start += ROW_SIZE;
+    /// Send the instructions of a [`Sequence`](sequence::Sequence).
+    fn run<Delay: DelayNs>(
+        &mut self,
+        sequence: &[Step],
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        for &step in sequence {
+            match step {
+                Step::Command(command, param) => self.write_command_param(command, param)?,
+                Step::Basic => self.select_basic()?,
+                Step::Extended => self.select_extended(delay)?,
+                Step::Wait(us) => self.wait_us(delay, us),
+                Step::Pause => self.flush_pause(delay),
             }
         }
         Ok(())
     }
 
-    /// Draw pixel
-    ///
-    /// Doesn't draw anything, if the x or y coordinates are off canvas.
-    ///
-    /// Supported values are 0 and (not 0)
-    #[inline]
-    pub fn set_pixel(&mut self, x: u8, y: u8, val: u8) {
-        if x < WIDTH as u8 && y < HEIGHT as u8 {
-            self.set_pixel_unchecked(x, y, val);
-        }
+    fn write_data(&mut self, data: u8) -> Result<(), Error<CommError, PinError>> {
+        self.interface.write_data(&[data])?;
+        Ok(())
     }
 
-    /// Draw pixel without canvas bounds checking.
-    ///
-    /// Supported values are 0 and (not 0)
-    ///
-    /// # Panics
-    ///
-    /// May panic or draw to undefined pixels, if x or y coordinates are off canvas.
-    #[inline]
-    pub fn set_pixel_unchecked(&mut self, mut x: u8, mut y: u8, val: u8) {
-        if self.flip {
-            y = (HEIGHT - 1) as u8 - y;
-            x = (WIDTH - 1) as u8 - x;
+    /// Wait between the instructions of a flush, see [`Timing::flush_us`].
+    fn flush_pause<Delay: DelayNs>(&self, delay: &mut Delay) {
+        if self.timing.flush_us > 0 {
+            delay.delay_us(self.timing.flush_us);
         }
-        let idx = y as usize * ROW_SIZE + x as usize / 8;
-        let x_mask = 0x80 >> (x % 8);
-        if val != 0 {
-            self.buffer[idx] |= x_mask;
+    }
+
+    /// Write data to GDRAM, pausing between the instructions if needed.
+    fn write_graphics<Delay: DelayNs>(
+        &mut self,
+        address: [u8; 2],
+        data: &[u8],
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if self.timing.flush_us == 0 {
+            self.interface.write_graphics(address, data)?;
         } else {
-            self.buffer[idx] &= !x_mask;
+            self.run(&graphics_address_sequence(address), delay)?;
+            self.interface.write_data(data)?;
+            self.flush_pause(delay);
         }
+        Ok(())
     }
 
-    #[inline]
-    fn do_flush(&mut self) -> Result<(), Error<SPIError, PinError>> {
-        for y in 0..HEIGHT as u8 / 2 {
-            self.set_address(0, y)?;
+    /// Clear whole display area and clears the buffer
+    pub fn clear<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.clear_buffer();
+        self.flush(delay)?;
+        Ok(())
+    }
 
-            let mut row_start = y as usize * ROW_SIZE;
-            for x in 0..ROW_SIZE {
-                self.write_data(self.buffer[row_start + x])?;
-            }
-            row_start += (HEIGHT as usize / 2) * ROW_SIZE;
-            for x in 0..ROW_SIZE {
-                self.write_data(self.buffer[row_start + x])?;
+    #[inline]
+    fn do_flush<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.run(&GRAPHICS_SEQUENCE, delay)?;
+        let mut row = [0; GDRAM_ROW_SIZE];
+        for y in 0..L::GDRAM_ROWS {
+            let mut word = 0;
+            while let Some((start, end)) = self.changed_words(y, word) {
+                let (address, len) = self.gdram_words(&mut row, y, start, end);
+                self.write_graphics(address, &row[..len], delay)?;
+                word = end;
             }
         }
+        self.update_shadow(Window::full::<L>());
         Ok(())
     }
 
     /// Flush buffer to update entire display
+    ///
+    /// Each GDRAM row is sent as one SPI write, 32 writes per frame on a 128x64 display.
+    /// With the `shadow-buffer` feature, only the words changed since the last flush are sent.
     pub fn flush<Delay: DelayNs>(
         &mut self,
         delay: &mut Delay,
-    ) -> Result<(), Error<SPIError, PinError>> {
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.can_flush() {
+            return Ok(());
+        }
         self.enable_cs(delay)?;
-        let result = self.do_flush();
+        let result = self.do_flush(delay);
         self.disable_cs(delay)?;
+        if result.is_ok() {
+            self.dirty = None;
+        }
         result
     }
 
     #[inline]
-    fn do_flush_region(
+    fn do_flush_region<Delay: DelayNs>(
         &mut self,
         x: u8,
-        mut y: u8,
+        y: u8,
         w: u8,
         h: u8,
-    ) -> Result<(), Error<SPIError, PinError>> {
-        let mut adj_x = x;
-        if self.flip {
-            y = HEIGHT as u8 - (y + h);
-            adj_x = WIDTH as u8 - (x + w);
-        }
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        let window = self.window(x, y, w, h);
+        self.do_flush_window(window, delay)
+    }
 
-        let mut left = adj_x - adj_x % X_ADDR_DIV;
-        let mut right = (adj_x + w) - 1;
-        right -= right % X_ADDR_DIV;
-        right += X_ADDR_DIV;
+    #[inline]
+    fn do_flush_window<Delay: DelayNs>(
+        &mut self,
+        window: Window,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.run(&GRAPHICS_SEQUENCE, delay)?;
 
-        if left > adj_x {
-            left -= X_ADDR_DIV; //make sure rightmost pixels are covered
+        let mut row = [0; GDRAM_ROW_SIZE];
+        for y in window.top..window.bottom {
+            let (address, len) = self.window_row(&mut row, window, y);
+            self.write_graphics(address, &row[..len], delay)?;
         }
+        self.update_shadow(window);
+        Ok(())
+    }
 
-        let mut row_start = y as usize * ROW_SIZE;
-        self.set_address(adj_x, y)?;
-        for y in y..(y + h) {
-            self.set_address(adj_x, y)?;
-
-            for x in left / 8..right / 8 {
-                self.write_data(self.buffer[row_start + x as usize])?;
+    /// Flush only the part of the buffer changed since the last flush
+    ///
+    /// Drawing keeps track of a bounding box of the changed pixels,
+    /// rounded to the 16 pixel words of the display memory.
+    /// Does nothing if nothing changed.
+    pub fn flush_dirty<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        match self.dirty {
+            Some(window) if self.can_flush() => {
+                self.enable_cs(delay)?;
+                let result = self.do_flush_window(window, delay);
+                self.disable_cs(delay)?;
+                if result.is_ok() {
+                    self.dirty = None;
+                }
+                result
             }
-
-            row_start += ROW_SIZE;
+            _ => Ok(()),
         }
-        Ok(())
     }
 
     /// Flush buffer to update region of the display
@@ -411,26 +1312,18 @@ where
         &mut self,
         x: u8,
         y: u8,
-        mut w: u8,
-        mut h: u8,
+        w: u8,
+        h: u8,
         delay: &mut Delay,
-    ) -> Result<(), Error<SPIError, PinError>> {
-        // Top-left is on screen and region has a width/height?
-        if x < WIDTH as u8 && y < HEIGHT as u8 && w > 0 && h > 0 {
-            // Limit width and height to right and bottom edge.
-            if x.saturating_add(w) > WIDTH as u8 {
-                w = WIDTH as u8 - x;
+    ) -> Result<(), Error<CommError, PinError>> {
+        match self.clip_region(x, y, w, h) {
+            Some((w, h)) if self.can_flush() => {
+                self.enable_cs(delay)?;
+                let result = self.do_flush_region(x, y, w, h, delay);
+                self.disable_cs(delay)?;
+                result
             }
-            if y.saturating_add(h) > HEIGHT as u8 {
-                h = HEIGHT as u8 - y;
-            }
-
-            self.enable_cs(delay)?;
-            let result = self.do_flush_region(x, y, w, h);
-            self.disable_cs(delay)?;
-            result
-        } else {
-            Ok(())
+            _ => Ok(()),
         }
     }
 }
@@ -441,26 +1334,30 @@ use embedded_graphics::{
 };
 
 #[cfg(feature = "graphics")]
-impl<SPI, CS, RST, PinError, SPIError> OriginDimensions for ST7920<SPI, CS, RST>
+impl<DI, RST, CS, L, B, S, PinError, CommError> OriginDimensions for ST7920<DI, RST, CS, L, B, S>
 where
-    SPI: SpiDevice<Error = SPIError>,
-    RST: OutputPin<Error = PinError>,
-    CS: OutputPin<Error = PinError>,
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
 {
     fn size(&self) -> Size {
-        Size {
-            width: WIDTH,
-            height: HEIGHT,
-        }
+        let (width, height) = self.dimensions();
+        Size { width, height }
     }
 }
 
 #[cfg(feature = "graphics")]
-impl<SPI, CS, RST, PinError, SPIError> DrawTarget for ST7920<SPI, CS, RST>
+impl<DI, RST, CS, L, B, S, PinError, CommError> DrawTarget for ST7920<DI, RST, CS, L, B, S>
 where
-    SPI: SpiDevice<Error = SPIError>,
-    RST: OutputPin<Error = PinError>,
-    CS: OutputPin<Error = PinError>,
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
 {
     type Error = core::convert::Infallible;
     type Color = BinaryColor;
@@ -469,12 +1366,17 @@ where
     where
         I: IntoIterator<Item = Pixel<Self::Color>>,
     {
+        if self.buffer.as_ref().is_empty() {
+            return Ok(());
+        }
+        #[cfg(not(feature = "graphics-unchecked"))]
+        let (width, height) = self.dimensions();
         for p in pixels {
             let Pixel(coord, color) = p;
 
             #[cfg(not(feature = "graphics-unchecked"))]
             let in_bounds =
-                coord.x >= 0 && coord.x < WIDTH as i32 && coord.y >= 0 && coord.y < HEIGHT as i32;
+                coord.x >= 0 && coord.x < width as i32 && coord.y >= 0 && coord.y < height as i32;
             #[cfg(feature = "graphics-unchecked")]
             let in_bounds = true;
 
@@ -494,17 +1396,20 @@ where
 }
 
 #[cfg(feature = "graphics")]
-impl<SPI, RST, CS, PinError, SPIError> ST7920<SPI, RST, CS>
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
 where
-    SPI: SpiDevice<Error = SPIError>,
+    DI: Interface<Error = CommError>,
     RST: OutputPin<Error = PinError>,
     CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
 {
     pub fn flush_region_graphics<Delay: DelayNs>(
         &mut self,
         region: (Point, Size),
         delay: &mut Delay,
-    ) -> Result<(), Error<SPIError, PinError>> {
+    ) -> Result<(), Error<CommError, PinError>> {
         let mut width: u32 = region.1.width;
         let mut height: u32 = region.1.height;
         let mut x: i32 = region.0.x;
@@ -527,3 +1432,86 @@ where
         self.flush_region(x as u8, y as u8, width as u8, height as u8, delay)
     }
 }
+
+/// Driver used by the unit tests of the buffer functions.
+#[cfg(test)]
+mod test_driver {
+    use core::convert::Infallible;
+
+    use crate::{Layout128x64, NoPin, Rotation, ST7920};
+
+    /// Bus of a driver that is never flushed.
+    pub(crate) struct Bus;
+
+    impl embedded_hal::spi::ErrorType for Bus {
+        type Error = Infallible;
+    }
+
+    pub(crate) type Driver = ST7920<Bus, NoPin, NoPin, Layout128x64, [u8; 1024], [u8; 1024]>;
+
+    /// Driver with the buffer filled with `fill` and an invalid shadow buffer.
+    pub(crate) fn driver(rotation: Rotation, fill: u8) -> Driver {
+        Driver::from_parts(Bus, None, None, rotation, [fill; 1024], [0; 1024])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_rect_rotation_and_mirror() {
+        let cases = [
+            (Rotation::Deg0, (false, false), (1, 2, 3, 4)),
+            (Rotation::Deg0, (true, false), (124, 2, 3, 4)),
+            (Rotation::Deg0, (false, true), (1, 58, 3, 4)),
+            (Rotation::Deg0, (true, true), (124, 58, 3, 4)),
+            (Rotation::Deg90, (false, false), (122, 1, 4, 3)),
+            (Rotation::Deg90, (true, false), (122, 60, 4, 3)),
+            (Rotation::Deg90, (false, true), (2, 1, 4, 3)),
+            (Rotation::Deg90, (true, true), (2, 60, 4, 3)),
+            (Rotation::Deg180, (false, false), (124, 58, 3, 4)),
+            (Rotation::Deg180, (true, false), (1, 58, 3, 4)),
+            (Rotation::Deg180, (false, true), (124, 2, 3, 4)),
+            (Rotation::Deg180, (true, true), (1, 2, 3, 4)),
+            (Rotation::Deg270, (false, false), (2, 60, 4, 3)),
+            (Rotation::Deg270, (true, false), (2, 1, 4, 3)),
+            (Rotation::Deg270, (false, true), (122, 60, 4, 3)),
+            (Rotation::Deg270, (true, true), (122, 1, 4, 3)),
+        ];
+        for &(rotation, mirror, expected) in cases.iter() {
+            assert_eq!(
+                map_rect::<Layout128x64>(rotation, mirror, 1, 2, 3, 4),
+                expected,
+                "{:?} {:?}",
+                rotation,
+                mirror
+            );
+        }
+    }
+
+    #[test]
+    fn map_rect_covers_buffer() {
+        let rotations = [
+            Rotation::Deg0,
+            Rotation::Deg90,
+            Rotation::Deg180,
+            Rotation::Deg270,
+        ];
+        for &rotation in rotations.iter() {
+            for &mirror in [(false, false), (true, false), (false, true), (true, true)].iter() {
+                let (width, height) = screen_size::<Layout128x32>(rotation);
+                let mut seen = [false; 128 * 32];
+                for y in 0..height {
+                    for x in 0..width {
+                        let (bx, by, w, h) = map_rect::<Layout128x32>(rotation, mirror, x, y, 1, 1);
+                        assert_eq!((w, h), (1, 1));
+                        let i = (by * 128 + bx) as usize;
+                        assert!(!seen[i], "{:?} {:?} maps twice to {}", rotation, mirror, i);
+                        seen[i] = true;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,365 @@
+//! Raster operations on the buffer.
+//!
+//! Images are packed 1 bit-per-pixel, stored row by row, each row starting at a byte boundary,
+//! with the leftmost pixel in the most significant bit.
+
+use embedded_hal::digital;
+
+use crate::interface;
+use crate::layout::Geometry;
+use crate::{Layout, Rotation, Window, ST7920};
+
+/// Combination of source pixels with the pixels in the buffer
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RasterOp {
+    /// Replace the buffer pixels
+    #[default]
+    Copy,
+    /// Set the buffer pixels where the source is set
+    Or,
+    /// Clear the buffer pixels where the source is clear
+    And,
+    /// Invert the buffer pixels where the source is set
+    Xor,
+    /// Clear the buffer pixels where the source is set
+    AndNot,
+}
+
+impl RasterOp {
+    /// Combine 8 buffer pixels with 8 source pixels.
+    fn apply(self, dst: u8, src: u8) -> u8 {
+        match self {
+            RasterOp::Copy => src,
+            RasterOp::Or => dst | src,
+            RasterOp::And => dst & src,
+            RasterOp::Xor => dst ^ src,
+            RasterOp::AndNot => dst & !src,
+        }
+    }
+}
+
+/// Bits `offset..offset + 8` of an image row, zero outside of the row.
+fn row_bits(row: &[u8], offset: i32) -> u8 {
+    let byte = |i: i32| {
+        if i >= 0 {
+            row.get(i as usize).copied().unwrap_or(0)
+        } else {
+            0
+        }
+    };
+    let i = offset.div_euclid(8);
+    let shift = offset.rem_euclid(8);
+    let word = (byte(i) as u16) << 8 | byte(i + 1) as u16;
+    (word << shift >> 8) as u8
+}
+
+/// Masks of the first and last byte of the bits `left..right` of a row.
+fn edge_masks(left: usize, right: usize) -> (u8, u8) {
+    (0xFF_u8 >> (left % 8), 0xFF_u8 << (7 - (right - 1) % 8))
+}
+
+/// Replace the bits of `dst` selected by `mask` with `combine(dst, src)`.
+fn combine_masked(dst: &mut u8, src: u8, mask: u8, combine: impl Fn(u8, u8) -> u8) {
+    *dst = *dst & !mask | combine(*dst, src) & mask;
+}
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Invert a buffer region
+    ///
+    /// If the region is completely off screen, nothing will be done.
+    /// If the given width or height are too big,
+    /// width and height will be trimmed to the screen dimensions.
+    pub fn invert_region(&mut self, x: u8, y: u8, w: u8, h: u8) {
+        self.apply_region(x, y, w, h, RasterOp::Xor);
+    }
+
+    /// Invert a pixel, e.g. for a blinking cursor
+    ///
+    /// Does nothing, if the pixel is off screen.
+    pub fn xor_pixel(&mut self, x: u8, y: u8) {
+        let (width, height) = self.dimensions();
+        if (x as u32) < width && (y as u32) < height && !self.buffer.as_ref().is_empty() {
+            self.combine_pixel(x, y, 0xFF, |dst, src| RasterOp::Xor.apply(dst, src));
+        }
+    }
+
+    /// Copy a packed 1 bit-per-pixel image of `w` by `h` pixels to the buffer at `x`, `y`
+    ///
+    /// Each row of `data` starts at a byte boundary, the leftmost pixel is the most significant bit.
+    /// The image is drawn with the current rotation and mirroring.
+    ///
+    /// If the region is completely off screen, nothing will be done.
+    /// If the image reaches beyond the screen or `data` ends early, the image is clipped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+    /// # let mut st7920 = st7920::ST7920::new(spi, reset, Some(cs), false);
+    /// // 10x2 image
+    /// let arrow = [0b0000_1000, 0b0000_0000, 0b1111_1111, 0b1100_0000];
+    /// st7920.blit(20, 10, 10, 2, &arrow);
+    /// # st7920.flush(&mut delay)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn blit(&mut self, x: u8, y: u8, w: u8, h: u8, data: &[u8]) {
+        self.blit_with(x, y, w, h, data, |_, src| src);
+    }
+
+    /// Combine a packed 1 bit-per-pixel image with the buffer, see [`blit`](Self::blit)
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+    /// # let mut st7920 = st7920::ST7920::new(spi, reset, Some(cs), false);
+    /// # let (x, y) = (60, 28);
+    /// # let cursor = [0b1000_0000, 0b1100_0000, 0b1110_0000, 0b1111_0000, 0b1111_1000, 0b1110_0000, 0b1011_0000, 0b0001_1000];
+    /// // Invert the pixels under the set pixels of the cursor
+    /// st7920.blit_op(x, y, 8, 8, &cursor, st7920::RasterOp::Xor);
+    /// # st7920.flush(&mut delay)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn blit_op(&mut self, x: u8, y: u8, w: u8, h: u8, data: &[u8], op: RasterOp) {
+        self.blit_with(x, y, w, h, data, move |dst, src| op.apply(dst, src));
+    }
+
+    /// Combine an image with the buffer, `combine` gets 8 buffer and 8 image pixels at a time.
+    fn blit_with<F>(&mut self, x: u8, y: u8, w: u8, h: u8, data: &[u8], combine: F)
+    where
+        F: Fn(u8, u8) -> u8 + Copy,
+    {
+        let stride = (w as usize).div_ceil(8);
+        if self.buffer.as_ref().is_empty() || stride == 0 {
+            return;
+        }
+        let h = h.min((data.len() / stride).min(u8::MAX as usize) as u8);
+        let (clipped_w, clipped_h) = match self.clip_region(x, y, w, h) {
+            Some(clipped) => clipped,
+            None => return,
+        };
+        self.mark_dirty(self.window(x, y, clipped_w, clipped_h));
+
+        let rows = data.chunks(stride).take(clipped_h as usize);
+        if self.rotation == Rotation::Deg0 && self.mirror() == (false, false) {
+            self.blit_rows(x, y, clipped_w, rows, combine);
+        } else {
+            for (row_y, row) in (y..).zip(rows) {
+                for i in 0..clipped_w {
+                    let bit = row[i as usize / 8] << (i % 8) & 0x80;
+                    let src = if bit != 0 { 0xFF } else { 0 };
+                    self.combine_pixel(x + i, row_y, src, combine);
+                }
+            }
+        }
+    }
+
+    /// Combine image rows with buffer rows starting at `y`, a byte of the buffer at a time.
+    fn blit_rows<'a, F>(
+        &mut self,
+        x: u8,
+        y: u8,
+        w: u8,
+        rows: impl Iterator<Item = &'a [u8]>,
+        combine: F,
+    ) where
+        F: Fn(u8, u8) -> u8 + Copy,
+    {
+        let left = x as usize;
+        let right = left + w as usize;
+        let start = left / 8;
+        let end = right.div_ceil(8);
+        let (start_mask, end_mask) = edge_masks(left, right);
+
+        let buffer = self.buffer.as_mut();
+        for (buffer_row, row) in (y as usize..).zip(rows) {
+            for column in start..end {
+                let mut mask = 0xFF_u8;
+                if column == start {
+                    mask &= start_mask;
+                }
+                if column == end - 1 {
+                    mask &= end_mask;
+                }
+                let bits = row_bits(row, (column * 8) as i32 - left as i32);
+                let byte = &mut buffer[buffer_row * L::ROW_SIZE + column];
+                combine_masked(byte, bits, mask, combine);
+            }
+        }
+    }
+
+    /// Combine a pixel given in screen coordinates with `src`, all bits set or clear.
+    fn combine_pixel(&mut self, x: u8, y: u8, src: u8, combine: impl Fn(u8, u8) -> u8) {
+        let (x, y, _, _) = self.buffer_rect(x, y, 1, 1);
+        let (x, y) = (x as u8, y as u8);
+        let idx = y as usize * L::ROW_SIZE + x as usize / 8;
+        combine_masked(
+            &mut self.buffer.as_mut()[idx],
+            src,
+            0x80 >> (x % 8),
+            combine,
+        );
+        self.mark_dirty(Window::pixel(x, y));
+    }
+
+    /// Combine a buffer region with set pixels, trimmed to the screen.
+    pub(crate) fn apply_region(&mut self, x: u8, y: u8, w: u8, h: u8, op: RasterOp) {
+        if self.buffer.as_ref().is_empty() {
+            return;
+        }
+        if let Some((w, h)) = self.clip_region(x, y, w, h) {
+            self.mark_dirty(self.window(x, y, w, h));
+
+            let (left, top, w, h) = self.buffer_rect(x, y, w, h);
+            let (left, top) = (left as usize, top as usize);
+            let right = left + w as usize;
+
+            let start = left / 8;
+            let end = right.div_ceil(8);
+            let (start_mask, end_mask) = edge_masks(left, right);
+
+            let buffer = self.buffer.as_mut();
+            for row in top..top + h as usize {
+                for column in start..end {
+                    let mut mask = 0xFF_u8;
+                    if column == start {
+                        mask &= start_mask;
+                    }
+                    if column == end - 1 {
+                        mask &= end_mask;
+                    }
+                    let byte = &mut buffer[row * L::ROW_SIZE + column];
+                    combine_masked(byte, 0xFF, mask, |dst, src| op.apply(dst, src));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_driver::{driver, Driver};
+
+    /// Pixel of the buffer at buffer coordinates.
+    fn pixel(st7920: &Driver, x: u32, y: u32) -> bool {
+        st7920.buffer[(y * 16 + x / 8) as usize] & (0x80 >> (x % 8)) != 0
+    }
+
+    #[test]
+    fn row_bits_shifts_across_bytes() {
+        let row = [0b1010_1010, 0b1111_0000];
+        assert_eq!(row_bits(&row, 0), 0b1010_1010);
+        assert_eq!(row_bits(&row, 4), 0b1010_1111);
+        assert_eq!(row_bits(&row, 12), 0);
+        assert_eq!(row_bits(&row, -3), 0b0001_0101);
+        assert_eq!(row_bits(&row, -8), 0);
+        assert_eq!(row_bits(&row, 16), 0);
+    }
+
+    #[test]
+    fn edge_masks_of_ranges() {
+        assert_eq!(edge_masks(0, 8), (0xFF, 0xFF));
+        assert_eq!(edge_masks(3, 5), (0b0001_1111, 0b1111_1000));
+        assert_eq!(edge_masks(5, 13), (0b0000_0111, 0b1111_1000));
+        assert_eq!(edge_masks(8, 16), (0xFF, 0xFF));
+    }
+
+    #[test]
+    fn apply_ops() {
+        let (dst, src) = (0b1100, 0b1010);
+        assert_eq!(RasterOp::Copy.apply(dst, src), 0b1010);
+        assert_eq!(RasterOp::Or.apply(dst, src), 0b1110);
+        assert_eq!(RasterOp::And.apply(dst, src), 0b1000);
+        assert_eq!(RasterOp::Xor.apply(dst, src), 0b0110);
+        assert_eq!(RasterOp::AndNot.apply(dst, src), 0b0100);
+    }
+
+    #[test]
+    fn blit_op_matches_pixels() {
+        // 10x3 image, each row starts at a byte boundary.
+        let image = [
+            0b1011_0011,
+            0b0100_0000, //
+            0b0110_1100,
+            0b1100_0000, //
+            0b1111_1111,
+            0b1000_0000,
+        ];
+        let image_pixel = |x: u32, y: u32| image[(y * 2 + x / 8) as usize] & (0x80 >> (x % 8)) != 0;
+        let ops = [
+            RasterOp::Copy,
+            RasterOp::Or,
+            RasterOp::And,
+            RasterOp::Xor,
+            RasterOp::AndNot,
+        ];
+        for &op in ops.iter() {
+            // The fast path at Deg0 and the pixel path at Deg180 have to agree.
+            let mut fast = driver(Rotation::Deg0, 0b0101_0101);
+            let mut slow = driver(Rotation::Deg180, 0b1010_1010);
+            fast.blit_op(5, 7, 10, 3, &image, op);
+            slow.blit_op(5, 7, 10, 3, &image, op);
+            for y in 0..64 {
+                for x in 0..128 {
+                    let inside = (5..15).contains(&x) && (7..10).contains(&y);
+                    let before = x % 2 == 1;
+                    let expected = if inside {
+                        let src = if image_pixel(x - 5, y - 7) { 0xFF } else { 0 };
+                        op.apply(before as u8 * 0xFF, src) != 0
+                    } else {
+                        before
+                    };
+                    assert_eq!(pixel(&fast, x, y), expected, "{:?} at {}, {}", op, x, y);
+                    assert_eq!(
+                        pixel(&slow, 127 - x, 63 - y),
+                        expected,
+                        "{:?} at {}, {} rotated",
+                        op,
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn blit_clips_to_screen_and_data() {
+        let mut st7920 = driver(Rotation::Deg0, 0);
+        st7920.blit(124, 62, 8, 4, &[0xFF; 3]);
+        for y in 0..64 {
+            for x in 0..128 {
+                assert_eq!(pixel(&st7920, x, y), x >= 124 && y >= 62);
+            }
+        }
+    }
+
+    #[test]
+    fn invert_region_and_xor_pixel() {
+        let mut st7920 = driver(Rotation::Deg0, 0);
+        st7920.invert_region(3, 1, 10, 2);
+        st7920.xor_pixel(3, 1);
+        st7920.xor_pixel(200, 1);
+        for y in 0..64 {
+            for x in 0..128 {
+                let inside = (3..13).contains(&x) && (1..3).contains(&y);
+                assert_eq!(pixel(&st7920, x, y), inside && (x, y) != (3, 1));
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Hardware vertical scroll.
+//!
+//! GDRAM has 64 rows, the panel shows 32 of them. With the scroll enabled,
+//! display row `r` shows GDRAM row `(r + offset) % 64`, so the rows below the
+//! visible ones can be written off-screen and scrolled in without sending a whole frame.
+//! Panels of 64 rows show the same GDRAM rows in both halves, each half scrolls on its own.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, OutputPin};
+
+use crate::interface::{self, Interface};
+use crate::layout::Geometry;
+use crate::sequence::GRAPHICS_SEQUENCE;
+use crate::{Error, Layout, GDRAM_ROW_SIZE, ST7920};
+
+/// Number of rows in GDRAM, including the off-screen ones.
+pub(crate) const GDRAM_HEIGHT: u8 = 64;
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: Interface<Error = CommError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Current scroll offset in GDRAM rows
+    pub fn scroll(&self) -> u8 {
+        self.scroll
+    }
+
+    /// The hardware scroll is enabled
+    pub fn is_scroll_enabled(&self) -> bool {
+        self.scroll_enabled
+    }
+
+    /// Scroll the display by `offset` GDRAM rows, modulo 64
+    ///
+    /// Takes effect when the scroll is enabled with [`enable_scroll`](Self::enable_scroll).
+    /// Flushes keep writing the buffer to the GDRAM rows starting at 0.
+    pub fn set_scroll<Delay: DelayNs>(
+        &mut self,
+        offset: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.scroll = offset % GDRAM_HEIGHT;
+        if !self.scroll_enabled {
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self.write_scroll(delay);
+        self.disable_cs(delay)?;
+        result
+    }
+
+    /// Enable or disable the hardware scroll
+    pub fn enable_scroll<Delay: DelayNs>(
+        &mut self,
+        enable: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.enable_cs(delay)?;
+        let result = self.do_enable_scroll(enable, delay);
+        self.disable_cs(delay)?;
+        result
+    }
+
+    pub(crate) fn do_enable_scroll<Delay: DelayNs>(
+        &mut self,
+        enable: bool,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.run(&self.enable_scroll_sequence(enable), delay)?;
+        self.scroll_enabled = enable;
+        Ok(())
+    }
+
+    fn write_scroll<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.run(&self.scroll_sequence(), delay)
+    }
+
+    /// Send `count` GDRAM rows of the buffer, starting at `source`,
+    /// to the GDRAM rows starting at `target`, wrapping at 64
+    ///
+    /// `source` is a row of the buffer in the layout of GDRAM, like the rows sent by [`flush`](Self::flush).
+    /// With a `target` of 32 or more, the rows are written off-screen to be scrolled in later.
+    pub fn flush_rows_to<Delay: DelayNs>(
+        &mut self,
+        source: u8,
+        count: u8,
+        target: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        let count = count.min(L::GDRAM_ROWS.saturating_sub(source));
+        if count == 0 || !self.can_flush() {
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self.do_flush_rows_to(source, count, target, delay);
+        self.disable_cs(delay)?;
+        result
+    }
+
+    fn do_flush_rows_to<Delay: DelayNs>(
+        &mut self,
+        source: u8,
+        count: u8,
+        target: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.run(&GRAPHICS_SEQUENCE, delay)?;
+
+        let mut row = [0; GDRAM_ROW_SIZE];
+        for i in 0..count {
+            let (address, len) = self.row_to(&mut row, source + i, target.wrapping_add(i));
+            self.write_graphics(address, &row[..len], delay)?;
+        }
+        self.rows_written_to(count, target);
+        Ok(())
+    }
+}
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Copy the GDRAM row `source` of the buffer to `row`,
+    /// returning the address of GDRAM row `target` and the length in bytes.
+    pub(crate) fn row_to(
+        &self,
+        row: &mut [u8; GDRAM_ROW_SIZE],
+        source: u8,
+        target: u8,
+    ) -> ([u8; 2], usize) {
+        let (mut address, len) = self.gdram_words(row, source, 0, L::GDRAM_ROW_WORDS);
+        address[0] = target % GDRAM_HEIGHT;
+        (address, len)
+    }
+
+    /// Record that `count` rows have been written to GDRAM starting at `target`.
+    pub(crate) fn rows_written_to(&mut self, count: u8, target: u8) {
+        if (0..count).any(|i| target.wrapping_add(i) % GDRAM_HEIGHT < L::GDRAM_ROWS) {
+            // The visible rows no longer match the buffer.
+            self.invalidate_shadow();
+        }
+    }
+}
@@ -0,0 +1,185 @@
+//! Instruction sequences shared by the blocking and the async driver.
+//!
+//! Each operation is built as a [`Sequence`] of steps from the driver state,
+//! both drivers only run the steps on their bus.
+
+use core::ops::Deref;
+
+use embedded_hal::digital;
+
+use crate::interface;
+use crate::{Instruction, Layout, ST7920};
+
+/// Step of an instruction [`Sequence`].
+#[derive(Clone, Copy)]
+pub(crate) enum Step {
+    /// Send an instruction with a parameter
+    Command(Instruction, u8),
+    /// Select the basic instruction set, if needed
+    Basic,
+    /// Select the extended instruction set, if needed, see [`ST7920::select_extended`]
+    Extended,
+    /// Wait for an instruction to complete, in µs
+    Wait(u32),
+    /// Wait between the instructions of a flush, see [`Timing::flush_us`](crate::Timing::flush_us)
+    Pause,
+}
+
+/// Steps of an operation.
+pub(crate) struct Sequence {
+    steps: [Step; 12],
+    len: usize,
+}
+
+impl Sequence {
+    fn new() -> Self {
+        Sequence {
+            steps: [Step::Wait(0); 12],
+            len: 0,
+        }
+    }
+
+    /// Append steps.
+    fn then(mut self, steps: &[Step]) -> Self {
+        self.steps[self.len..self.len + steps.len()].copy_from_slice(steps);
+        self.len += steps.len();
+        self
+    }
+}
+
+impl Deref for Sequence {
+    type Target = [Step];
+
+    fn deref(&self) -> &[Step] {
+        &self.steps[..self.len]
+    }
+}
+
+/// Setting the GDRAM address one instruction at a time, before writing the data of a row.
+pub(crate) fn graphics_address_sequence(address: [u8; 2]) -> [Step; 4] {
+    [
+        Step::Command(Instruction::SetGraphicsAddress, address[0]),
+        Step::Pause,
+        Step::Command(Instruction::SetGraphicsAddress, address[1]),
+        Step::Pause,
+    ]
+}
+
+/// Start of every write to GDRAM.
+pub(crate) const GRAPHICS_SEQUENCE: [Step; 2] = [Step::Extended, Step::Pause];
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Track the controller state changed by an instruction.
+    pub(crate) fn command_sent(&mut self, command: Instruction) {
+        match command {
+            Instruction::BasicFunction => self.extended = false,
+            Instruction::ExtendedFunction | Instruction::GraphicsOn => self.extended = true,
+            _ => {}
+        }
+        // Any instruction ends the standby.
+        self.asleep = false;
+    }
+
+    /// Bring the controller to a known state without a reset pin:
+    /// graphics display off, basic instruction set.
+    pub(crate) fn soft_reset_sequence(&self) -> Sequence {
+        let wait = Step::Wait(self.timing.function_set_us);
+        Sequence::new().then(&[
+            Step::Command(Instruction::ExtendedFunction, 0),
+            wait,
+            Step::Command(Instruction::BasicFunction, 0),
+            wait,
+        ])
+    }
+
+    /// Set up the basic instruction set after a reset,
+    /// then switch the graphics display on if the mode shows graphics,
+    /// instruction set first, then the graphics display.
+    pub(crate) fn init_sequence(&self) -> Sequence {
+        let timing = &self.timing;
+        let sequence = Sequence::new().then(&[
+            Step::Command(Instruction::BasicFunction, 0),
+            Step::Wait(timing.function_set_us),
+            Step::Command(Instruction::DisplayOnCursorOff, 0),
+            Step::Wait(timing.command_us),
+            Step::Command(Instruction::ClearScreen, 0),
+            Step::Wait(timing.init_ms * 1000),
+            Step::Command(Instruction::EntryMode, 0),
+            Step::Wait(timing.command_us),
+        ]);
+        if self.mode.has_graphics() {
+            sequence.then(&[
+                Step::Command(Instruction::ExtendedFunction, 0),
+                Step::Wait(timing.init_ms * 1000),
+                Step::Command(Instruction::GraphicsOn, 0),
+                Step::Wait(timing.graphics_on_ms * 1000),
+            ])
+        } else {
+            sequence
+        }
+    }
+
+    pub(crate) fn sleep_sequence(&self) -> Sequence {
+        let wait = Step::Wait(self.timing.command_us);
+        Sequence::new().then(&[
+            Step::Extended,
+            wait,
+            Step::Command(Instruction::Standby, 0),
+            wait,
+        ])
+    }
+
+    /// Leave the standby, restoring the display state and the mode.
+    pub(crate) fn wake_sequence(&self) -> Sequence {
+        let wait = Step::Wait(self.timing.command_us);
+        let sequence = Sequence::new()
+            .then(&[Step::Command(Instruction::BasicFunction, 0), wait])
+            .then(&self.display_on_sequence(self.display_on));
+        if self.mode.has_graphics() {
+            sequence.then(&[Step::Extended, wait])
+        } else {
+            sequence
+        }
+    }
+
+    pub(crate) fn display_on_sequence(&self, on: bool) -> Sequence {
+        let wait = Step::Wait(self.timing.command_us);
+        let command = if on {
+            Instruction::DisplayOnCursorOff
+        } else {
+            Instruction::DisplayOff
+        };
+        Sequence::new().then(&[Step::Basic, wait, Step::Command(command, 0), wait])
+    }
+
+    pub(crate) fn enable_scroll_sequence(&self, enable: bool) -> Sequence {
+        let wait = Step::Wait(self.timing.command_us);
+        let sequence = Sequence::new().then(&[
+            Step::Extended,
+            wait,
+            Step::Command(Instruction::ScrollSelect, enable as u8),
+            wait,
+        ]);
+        if enable {
+            sequence.then(&self.scroll_sequence())
+        } else {
+            sequence
+        }
+    }
+
+    pub(crate) fn scroll_sequence(&self) -> Sequence {
+        Sequence::new().then(&[
+            Step::Extended,
+            Step::Command(Instruction::SetScrollAddress, self.scroll),
+            Step::Wait(self.timing.command_us),
+        ])
+    }
+}
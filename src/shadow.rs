@@ -0,0 +1,154 @@
+//! Shadow copy of the display memory, enabled by the `shadow-buffer` feature.
+//!
+//! With the shadow buffer, a full flush compares the buffer with what was sent before
+//! and sends only the changed words of the display memory.
+//! Without it, the shadow storage of [`Layout::Shadow`] is empty and every word is sent.
+
+use embedded_hal::digital;
+
+use crate::interface;
+use crate::layout::Geometry;
+use crate::{Layout, Window, ST7920};
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Buffer index of a word of a GDRAM row.
+    fn word_index(y: u8, word: u8) -> usize {
+        let row = y as usize + (word / L::ROW_WORDS) as usize * 32;
+        row * L::ROW_SIZE + (word % L::ROW_WORDS) as usize * 2
+    }
+
+    fn word_changed(&self, y: u8, word: u8) -> bool {
+        let i = Self::word_index(y, word);
+        self.front()[i..i + 2] != self.shadow.as_ref()[i..i + 2]
+    }
+
+    /// Next run of words of the `y`th GDRAM row to send in a full flush,
+    /// starting at word `from`.
+    pub(crate) fn changed_words(&self, y: u8, from: u8) -> Option<(u8, u8)> {
+        if !self.shadow_valid {
+            return if from == 0 {
+                Some((0, L::GDRAM_ROW_WORDS))
+            } else {
+                None
+            };
+        }
+
+        let start = (from..L::GDRAM_ROW_WORDS).find(|&word| self.word_changed(y, word))?;
+        let mut end = start + 1;
+        while end < L::GDRAM_ROW_WORDS {
+            if self.word_changed(y, end) {
+                end += 1;
+            } else if end + 1 < L::GDRAM_ROW_WORDS && self.word_changed(y, end + 1) {
+                // Sending a single unchanged word is cheaper than setting the address again.
+                end += 2;
+            } else {
+                break;
+            }
+        }
+        Some((start, end))
+    }
+
+    /// Record that a window of the buffer has been sent to the display.
+    pub(crate) fn update_shadow(&mut self, window: Window) {
+        if self.shadow.as_ref().is_empty() {
+            return;
+        }
+        for y in window.top..window.bottom {
+            let row_start = y as usize * L::ROW_SIZE;
+            let range = row_start + window.left as usize * 2..row_start + window.right as usize * 2;
+            let front = self.front.as_ref().unwrap_or(&self.buffer).as_ref();
+            self.shadow.as_mut()[range.clone()].copy_from_slice(&front[range]);
+        }
+        if window == Window::full::<L>() {
+            self.shadow_valid = true;
+        }
+    }
+
+    /// Send the whole buffer on the next flush
+    ///
+    /// Needed if the display memory was changed without this driver, e.g. by a reset of the display.
+    pub fn invalidate_shadow(&mut self) {
+        self.shadow_valid = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_driver::{driver, Driver};
+    use crate::{Layout128x64, Rotation, Window};
+
+    /// Driver with a valid shadow buffer and `words` of GDRAM row 0 changed since.
+    fn changed(words: &[usize]) -> Driver {
+        let mut st7920 = driver(Rotation::Deg0, 0);
+        st7920.update_shadow(Window::full::<Layout128x64>());
+        for &word in words {
+            let i = Driver::word_index(0, word as u8);
+            st7920.buffer[i] = 0xFF;
+        }
+        st7920
+    }
+
+    /// All runs of GDRAM row 0 sent by a full flush.
+    fn runs(st7920: &Driver) -> [Option<(u8, u8)>; 4] {
+        let mut runs = [None; 4];
+        let mut word = 0;
+        for run in runs.iter_mut() {
+            *run = st7920.changed_words(0, word);
+            match *run {
+                Some((_, end)) => word = end,
+                None => break,
+            }
+        }
+        runs
+    }
+
+    #[test]
+    fn unchanged() {
+        assert_eq!(runs(&changed(&[])), [None; 4]);
+    }
+
+    #[test]
+    fn invalid_sends_whole_row() {
+        let mut st7920 = changed(&[]);
+        st7920.invalidate_shadow();
+        assert_eq!(runs(&st7920), [Some((0, 16)), None, None, None]);
+    }
+
+    #[test]
+    fn single_word() {
+        assert_eq!(runs(&changed(&[2])), [Some((2, 3)), None, None, None]);
+        assert_eq!(runs(&changed(&[15])), [Some((15, 16)), None, None, None]);
+    }
+
+    #[test]
+    fn merges_across_one_unchanged_word() {
+        assert_eq!(runs(&changed(&[2, 4])), [Some((2, 5)), None, None, None]);
+        assert_eq!(
+            runs(&changed(&[13, 15])),
+            [Some((13, 16)), None, None, None]
+        );
+    }
+
+    #[test]
+    fn splits_at_two_unchanged_words() {
+        assert_eq!(
+            runs(&changed(&[2, 5, 6, 9])),
+            [Some((2, 3)), Some((5, 7)), Some((9, 10)), None]
+        );
+    }
+
+    #[test]
+    fn continues_in_lower_half() {
+        // Words 8 to 15 of a GDRAM row are buffer row 32 of a 128x64 panel.
+        assert_eq!(runs(&changed(&[7, 8])), [Some((7, 9)), None, None, None]);
+        assert_eq!(Driver::word_index(0, 8), 32 * 16);
+    }
+}
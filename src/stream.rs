@@ -0,0 +1,285 @@
+//! Drawing without a buffer of the size of the display.
+//!
+//! A [`Drawable`] is rendered band by band into a small scratch buffer supplied by the caller,
+//! each band is sent to GDRAM before the next one is rendered.
+//! The drawable is drawn once per band, trading CPU time for memory.
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use embedded_graphics::{pixelcolor::BinaryColor, prelude::*};
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, OutputPin};
+
+use crate::interface::{self, Interface};
+use crate::layout::Geometry;
+use crate::sequence::GRAPHICS_SEQUENCE;
+use crate::{graphics_address, map_rect, screen_size, Error, Layout, Rotation, Window, ST7920};
+
+/// Buffer storage of a driver that only draws with [`ST7920::draw_streamed`]
+///
+/// The buffer functions of such a driver do nothing. It has no shadow buffer either,
+/// so its type is `ST7920<DI, RST, CS, L, Unbuffered, Unbuffered>`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use st7920::{Layout128x64, ST7920};
+///
+/// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+/// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+/// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+/// # use embedded_graphics::{mono_font::{ascii::FONT_6X10, MonoTextStyle}, pixelcolor::BinaryColor, prelude::*, text::Text};
+/// # let text = Text::new("Hello", Point::new(0, 10), MonoTextStyle::new(&FONT_6X10, BinaryColor::On));
+/// let mut st7920 = ST7920::new_unbuffered(spi, reset, Some(cs), false, Layout128x64);
+/// let mut scratch = [0; 16 * 16];
+/// st7920.draw_streamed(&text, &mut scratch, &mut delay)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Unbuffered;
+
+impl AsRef<[u8]> for Unbuffered {
+    fn as_ref(&self) -> &[u8] {
+        &[]
+    }
+}
+
+impl AsMut<[u8]> for Unbuffered {
+    fn as_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
+}
+
+/// Rows `top..top + rows` of the buffer, drawn in screen coordinates.
+struct Band<'a, L> {
+    data: &'a mut [u8],
+    top: u32,
+    rows: u32,
+    rotation: Rotation,
+    mirror: (bool, bool),
+    layout: PhantomData<L>,
+}
+
+impl<L: Layout> OriginDimensions for Band<'_, L> {
+    fn size(&self) -> Size {
+        let (width, height) = screen_size::<L>(self.rotation);
+        Size::new(width, height)
+    }
+}
+
+impl<L: Layout> DrawTarget for Band<'_, L> {
+    type Error = Infallible;
+    type Color = BinaryColor;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        let (width, height) = screen_size::<L>(self.rotation);
+        for Pixel(coord, color) in pixels {
+            if coord.x < 0 || coord.y < 0 || coord.x as u32 >= width || coord.y as u32 >= height {
+                continue;
+            }
+            let (x, y, _, _) = map_rect::<L>(
+                self.rotation,
+                self.mirror,
+                coord.x as u32,
+                coord.y as u32,
+                1,
+                1,
+            );
+            if y < self.top || y >= self.top + self.rows {
+                continue;
+            }
+            let idx = (y - self.top) as usize * L::ROW_SIZE + x as usize / 8;
+            let mask = 0x80 >> (x % 8);
+            match color {
+                BinaryColor::On => self.data[idx] |= mask,
+                BinaryColor::Off => self.data[idx] &= !mask,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<DI, RST, PinError, CommError, CS, L> ST7920<DI, RST, CS, L, Unbuffered, Unbuffered>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+{
+    /// Create a new driver instance without a buffer, see [`Unbuffered`].
+    pub fn new_unbuffered(interface: DI, rst: RST, cs: Option<CS>, flip: bool, _layout: L) -> Self {
+        let rotation = if flip {
+            Rotation::Deg180
+        } else {
+            Rotation::Deg0
+        };
+        Self::from_parts(interface, Some(rst), cs, rotation, Unbuffered, Unbuffered)
+    }
+}
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Number of buffer rows rendered at once into a scratch buffer of `len` bytes.
+    pub(crate) fn band_rows(len: usize) -> u32 {
+        let rows = (len / L::ROW_SIZE) as u32;
+        assert!(rows > 0, "scratch buffer is smaller than a row");
+        rows.min(L::HEIGHT)
+    }
+
+    /// Render the buffer rows starting at `top` into `band`, cleared first.
+    pub(crate) fn render_band<D>(&self, drawable: &D, band: &mut [u8], top: u32)
+    where
+        D: Drawable<Color = BinaryColor>,
+    {
+        band.fill(0);
+        let mut target = Band::<L> {
+            rows: (band.len() / L::ROW_SIZE) as u32,
+            data: band,
+            top,
+            rotation: self.rotation,
+            mirror: self.mirror(),
+            layout: PhantomData,
+        };
+        let _ = drawable.draw(&mut target);
+    }
+}
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: Interface<Error = CommError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Draw directly to the display, rendering a band of rows at a time into `scratch`
+    ///
+    /// The whole display is replaced by the drawing, with the current rotation and mirroring.
+    /// The band is as high as the number of whole buffer rows fitting into `scratch`,
+    /// e.g. 256 bytes hold 16 rows of a 128 pixels wide panel.
+    /// The buffer is not changed, so the next flush or [`flush_dirty`](Self::flush_dirty)
+    /// sends the whole buffer again.
+    ///
+    /// Does nothing, if the display is in [`Mode::Text`](crate::Mode::Text) or asleep.
+    ///
+    /// # Panics
+    ///
+    /// If `scratch` is smaller than a row of the panel.
+    pub fn draw_streamed<D, Delay>(
+        &mut self,
+        drawable: &D,
+        scratch: &mut [u8],
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>>
+    where
+        D: Drawable<Color = BinaryColor>,
+        Delay: DelayNs,
+    {
+        let rows = Self::band_rows(scratch.len());
+        if !self.shows_graphics() {
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self.do_draw_streamed(drawable, scratch, rows, delay);
+        self.disable_cs(delay)?;
+        self.invalidate_shadow();
+        self.mark_dirty(Window::full::<L>());
+        result
+    }
+
+    fn do_draw_streamed<D, Delay>(
+        &mut self,
+        drawable: &D,
+        scratch: &mut [u8],
+        rows: u32,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>>
+    where
+        D: Drawable<Color = BinaryColor>,
+        Delay: DelayNs,
+    {
+        self.run(&GRAPHICS_SEQUENCE, delay)?;
+
+        let mut top = 0;
+        while top < L::HEIGHT {
+            let rows = rows.min(L::HEIGHT - top);
+            let band = &mut scratch[..rows as usize * L::ROW_SIZE];
+            self.render_band(drawable, band, top);
+            for (y, data) in (top..).zip(band.chunks_exact(L::ROW_SIZE)) {
+                self.write_graphics(graphics_address::<L>(0, y as u8), data, delay)?;
+            }
+            top += rows;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Layout128x64;
+
+    fn draw(band: &mut Band<'_, Layout128x64>, pixels: &[(i32, i32, BinaryColor)]) {
+        let pixels = pixels
+            .iter()
+            .map(|&(x, y, color)| Pixel(Point::new(x, y), color));
+        band.draw_iter(pixels).unwrap();
+    }
+
+    fn band(data: &mut [u8], top: u32, rotation: Rotation) -> Band<'_, Layout128x64> {
+        Band {
+            rows: (data.len() / 16) as u32,
+            data,
+            top,
+            rotation,
+            mirror: (false, false),
+            layout: PhantomData,
+        }
+    }
+
+    #[test]
+    fn clips_to_band() {
+        let mut data = [0; 32];
+        draw(
+            &mut band(&mut data, 16, Rotation::Deg0),
+            &[
+                (0, 15, BinaryColor::On),
+                (0, 16, BinaryColor::On),
+                (9, 17, BinaryColor::On),
+                (0, 18, BinaryColor::On),
+                (-1, 16, BinaryColor::On),
+                (128, 16, BinaryColor::On),
+            ],
+        );
+        let mut expected = [0; 32];
+        expected[0] = 0x80;
+        expected[16 + 1] = 0x40;
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn rotated() {
+        // Screen (x, y) is buffer (127 - y, x) when rotated by 90 degrees.
+        let mut data = [0xFF; 16 * 8];
+        draw(
+            &mut band(&mut data, 0, Rotation::Deg90),
+            &[(5, 0, BinaryColor::Off), (20, 0, BinaryColor::Off)],
+        );
+        let mut expected = [0xFF; 16 * 8];
+        expected[5 * 16 + 15] = 0xFE;
+        assert_eq!(data, expected);
+    }
+}
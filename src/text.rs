@@ -0,0 +1,508 @@
+//! Text mode using the character ROM (CGROM) of the controller.
+//!
+//! DDRAM holds 16 bit words, each showing two half-width 8x16 characters.
+//! Text lines are laid out in DDRAM like the pixel rows in GDRAM.
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::{self, OutputPin};
+
+use crate::interface::{self, Interface};
+use crate::layout::Geometry;
+use crate::{Error, Instruction, Layout, ST7920};
+
+#[cfg(feature = "graphics")]
+use embedded_graphics::{
+    image::{Image, ImageRaw},
+    pixelcolor::BinaryColor,
+    prelude::*,
+};
+
+/// Number of characters of the largest layout.
+pub(crate) const TEXT_SIZE: usize = 64;
+
+/// DDRAM word address of the first character of a line.
+///
+/// Lines below the first two continue the DDRAM lines to the right.
+fn line_address<L: Layout>(line: u8) -> u8 {
+    (line % 2) * 0x10 + (line / 2) * L::ROW_WORDS
+}
+
+/// DDRAM row of a line, the parameter of [`Instruction::Reverse`].
+fn reverse_row<L: Layout>(line: u8) -> u8 {
+    line_address::<L>(line) / 0x10
+}
+
+/// One of the four user-defined 16x16 characters in CGRAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CgramSlot {
+    Slot0,
+    Slot1,
+    Slot2,
+    Slot3,
+}
+
+impl CgramSlot {
+    fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// Character code of the slot in DDRAM.
+    fn code(self) -> [u8; 2] {
+        [0x00, self.index() * 2]
+    }
+}
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: interface::ErrorType<Error = CommError>,
+    RST: digital::ErrorType<Error = PinError>,
+    CS: digital::ErrorType<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    pub(crate) fn reset_text(&mut self) {
+        self.text = [b' '; TEXT_SIZE];
+        self.cursor = (0, 0);
+    }
+}
+
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: Interface<Error = CommError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Move the DDRAM address counter to the cursor.
+    ///
+    /// A word can only be written as a whole, so for an odd column
+    /// the preceding character is written again.
+    pub(crate) fn sync_cursor(&mut self) -> Result<(), Error<CommError, PinError>> {
+        let (line, col) = self.cursor;
+        self.select_basic()?;
+        self.write_command_param(
+            Instruction::SetDDRAMAddress,
+            line_address::<L>(line) + col / 2,
+        )?;
+        if col % 2 != 0 {
+            let prev = self.text[(line * L::TEXT_COLUMNS + col - 1) as usize];
+            self.write_data(prev)?;
+        }
+        Ok(())
+    }
+
+    /// Make sure the address counter is at the cursor before writing text.
+    fn resume_text(&mut self) -> Result<(), Error<CommError, PinError>> {
+        if self.extended {
+            // A flush in mixed mode moved the address counter.
+            self.sync_cursor()?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn do_write_str(&mut self, s: &str) -> Result<(), Error<CommError, PinError>> {
+        self.resume_text()?;
+        for c in s.chars() {
+            let line = self.cursor.0;
+            if c == '\n' {
+                self.cursor = ((line + 1) % L::TEXT_LINES, 0);
+                self.sync_cursor()?;
+                continue;
+            }
+
+            let code = if c == ' ' || c.is_ascii_graphic() {
+                c as u8
+            } else {
+                b'?'
+            };
+            self.write_code(code)?;
+        }
+        Ok(())
+    }
+
+    /// Write one byte of a character code at the cursor and advance it.
+    fn write_code(&mut self, code: u8) -> Result<(), Error<CommError, PinError>> {
+        let (line, col) = self.cursor;
+        self.write_data(code)?;
+        self.text[(line * L::TEXT_COLUMNS + col) as usize] = code;
+
+        // Lines are not consecutive in DDRAM, so the address has to be set on wrap.
+        if col + 1 < L::TEXT_COLUMNS {
+            self.cursor = (line, col + 1);
+        } else {
+            self.cursor = ((line + 1) % L::TEXT_LINES, 0);
+            self.sync_cursor()?;
+        }
+        Ok(())
+    }
+
+    /// Write a two byte, full-width character code at the cursor.
+    ///
+    /// Full-width characters occupy a whole DDRAM word,
+    /// so a space is inserted if the cursor is at an odd column.
+    fn write_wide_code(&mut self, code: [u8; 2]) -> Result<(), Error<CommError, PinError>> {
+        if self.cursor.1 & 1 != 0 {
+            self.write_code(b' ')?;
+        }
+        self.write_code(code[0])?;
+        self.write_code(code[1])
+    }
+
+    /// Set the text cursor
+    ///
+    /// Does nothing, if the display is in [`Mode::Graphics`](crate::Mode::Graphics)
+    /// or the line or column is off screen.
+    pub fn set_cursor<Delay: DelayNs>(
+        &mut self,
+        line: u8,
+        col: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.mode.has_text() || line >= L::TEXT_LINES || col >= L::TEXT_COLUMNS {
+            return Ok(());
+        }
+        self.cursor = (line, col);
+        self.enable_cs(delay)?;
+        let result = self.sync_cursor();
+        self.disable_cs(delay)?;
+        result
+    }
+
+    /// Write text at the cursor using the built-in font
+    ///
+    /// Text wraps to the next line at the end of a line and on `'\n'`.
+    /// Characters which are not printable ASCII are shown as `'?'`.
+    ///
+    /// Does nothing, if the display is in [`Mode::Graphics`](crate::Mode::Graphics).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE> {
+    /// let mut st7920 = st7920::ST7920::new(spi, reset, Some(cs), false);
+    /// st7920.init(&mut delay)?;
+    /// st7920.set_mode(st7920::Mode::Text, &mut delay)?;
+    /// st7920.set_cursor(1, 0, &mut delay)?;
+    /// st7920.write_str("Battery: 87%", &mut delay)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_str<Delay: DelayNs>(
+        &mut self,
+        s: &str,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.mode.has_text() {
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self.do_write_str(s);
+        self.disable_cs(delay)?;
+        result
+    }
+
+    #[inline]
+    fn do_write_gb2312(&mut self, codes: &[u8]) -> Result<(), Error<CommError, PinError>> {
+        self.sync_cursor()?;
+        let mut codes = codes.iter();
+        while let Some(&code) = codes.next() {
+            if code < 0x80 {
+                self.write_code(code)?;
+            } else if let Some(&second) = codes.next() {
+                self.write_wide_code([code, second])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write character codes for the Chinese font ROM at the given position
+    ///
+    /// Bytes below 0x80 are half-width characters,
+    /// other bytes start a two byte full-width character code (GB2312 or BIG5, depending on the ROM).
+    /// Full-width characters start at even columns, a space is inserted when needed.
+    /// The cursor is left after the written text.
+    /// Use [`gb2312::encode`](crate::gb2312::encode) to get the codes of a string.
+    ///
+    /// Does nothing, if the display is in [`Mode::Graphics`](crate::Mode::Graphics)
+    /// or the line or column is off screen.
+    pub fn write_gb2312<Delay: DelayNs>(
+        &mut self,
+        line: u8,
+        col: u8,
+        codes: &[u8],
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.mode.has_text() || line >= L::TEXT_LINES || col >= L::TEXT_COLUMNS {
+            return Ok(());
+        }
+        self.cursor = (line, col);
+        self.enable_cs(delay)?;
+        let result = self.do_write_gb2312(codes);
+        self.disable_cs(delay)?;
+        result
+    }
+
+    /// Text line shown reversed, see [`reverse_line`](Self::reverse_line)
+    pub fn reversed_line(&self) -> Option<u8> {
+        self.reversed
+    }
+
+    /// Show a text line reversed, e.g. to highlight a menu entry
+    ///
+    /// The previously reversed line is shown normally again.
+    /// The controller reverses whole DDRAM rows, so on a 128x64 panel
+    /// lines 0 and 2 and lines 1 and 3 are reversed together.
+    ///
+    /// Does nothing, if the display is in [`Mode::Graphics`](crate::Mode::Graphics)
+    /// or the line is off screen.
+    pub fn reverse_line<Delay: DelayNs>(
+        &mut self,
+        line: u8,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.mode.has_text() || line >= L::TEXT_LINES {
+            return Ok(());
+        }
+        self.set_reversed(Some(line), delay)
+    }
+
+    /// Show all text lines normally
+    ///
+    /// Does nothing, if the display is in [`Mode::Graphics`](crate::Mode::Graphics).
+    pub fn clear_reverse<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.mode.has_text() {
+            return Ok(());
+        }
+        self.set_reversed(None, delay)
+    }
+
+    fn set_reversed<Delay: DelayNs>(
+        &mut self,
+        line: Option<u8>,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if line.map(reverse_row::<L>) == self.reversed.map(reverse_row::<L>) {
+            self.reversed = line;
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self.do_set_reversed(line, delay);
+        self.disable_cs(delay)?;
+        result
+    }
+
+    fn do_set_reversed<Delay: DelayNs>(
+        &mut self,
+        line: Option<u8>,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.select_extended(delay)?;
+        self.wait_us(delay, self.timing.command_us);
+        // Reversing a row again shows it normally.
+        for toggle in [self.reversed, line].iter().flatten() {
+            self.write_command_param(Instruction::Reverse, reverse_row::<L>(*toggle))?;
+            self.wait_us(delay, self.timing.command_us);
+        }
+        self.reversed = line;
+        Ok(())
+    }
+
+    /// Clear the text and move the cursor to the top-left corner
+    ///
+    /// Does nothing, if the display is in [`Mode::Graphics`](crate::Mode::Graphics).
+    pub fn clear_text<Delay: DelayNs>(
+        &mut self,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.mode.has_text() {
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self
+            .select_basic()
+            .and_then(|_| self.write_command(Instruction::ClearScreen));
+        self.wait_us(delay, self.timing.clear_us);
+        self.disable_cs(delay)?;
+        self.reset_text();
+        result
+    }
+
+    #[inline]
+    fn do_upload_glyph<Delay: DelayNs>(
+        &mut self,
+        slot: CgramSlot,
+        bitmap: &[u16; 16],
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        let scroll_enabled = self.scroll_enabled;
+        if scroll_enabled {
+            // The CGRAM address can only be set while the scroll is disabled.
+            self.do_enable_scroll(false, delay)?;
+        }
+        self.select_basic()?;
+        self.write_command_param(Instruction::SetCGRAMAddress, slot.index() << 4)?;
+        for row in bitmap {
+            self.write_data((row >> 8) as u8)?;
+            self.write_data(*row as u8)?;
+        }
+        if scroll_enabled {
+            self.wait_us(delay, self.timing.command_us);
+            self.do_enable_scroll(true, delay)?;
+        }
+        // Writing CGRAM moved the address counter away from the text cursor.
+        if self.mode.has_text() {
+            self.sync_cursor()?;
+        }
+        Ok(())
+    }
+
+    /// Upload a 16x16 custom character to CGRAM
+    ///
+    /// Each `u16` is one row, the most significant bit is the leftmost pixel.
+    /// Characters already shown from the slot change immediately.
+    pub fn upload_glyph<Delay: DelayNs>(
+        &mut self,
+        slot: CgramSlot,
+        bitmap: &[u16; 16],
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        self.enable_cs(delay)?;
+        let result = self.do_upload_glyph(slot, bitmap, delay);
+        self.disable_cs(delay)?;
+        result
+    }
+
+    /// Show a custom character from CGRAM at the cursor
+    ///
+    /// The character is as wide as two columns and starts at an even column,
+    /// if the cursor is at an odd column a space is written first.
+    ///
+    /// Does nothing, if the display is in [`Mode::Graphics`](crate::Mode::Graphics).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+    /// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+    /// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+    /// # use st7920::CgramSlot;
+    /// # const BATTERY_ICON: [u16; 16] = [0x0FF0, 0x0810, 0x3FFC, 0x2004, 0x2FF4, 0x2FF4, 0x2FF4, 0x2FF4, 0x2004, 0x2FF4, 0x2FF4, 0x2FF4, 0x2004, 0x3FFC, 0, 0];
+    /// let mut st7920 = st7920::ST7920::new(spi, reset, Some(cs), false);
+    /// st7920.init(&mut delay)?;
+    /// st7920.set_mode(st7920::Mode::Text, &mut delay)?;
+    /// st7920.upload_glyph(CgramSlot::Slot0, &BATTERY_ICON, &mut delay)?;
+    /// st7920.set_cursor(0, 14, &mut delay)?;
+    /// st7920.write_glyph(CgramSlot::Slot0, &mut delay)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_glyph<Delay: DelayNs>(
+        &mut self,
+        slot: CgramSlot,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        if !self.mode.has_text() {
+            return Ok(());
+        }
+        self.enable_cs(delay)?;
+        let result = self
+            .resume_text()
+            .and_then(|_| self.write_wide_code(slot.code()));
+        self.disable_cs(delay)?;
+        result
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl<DI, RST, CS, L, B, S, PinError, CommError> ST7920<DI, RST, CS, L, B, S>
+where
+    DI: Interface<Error = CommError>,
+    RST: OutputPin<Error = PinError>,
+    CS: OutputPin<Error = PinError>,
+    L: Layout,
+    B: AsRef<[u8]> + AsMut<[u8]>,
+    S: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Upload a 16x16 custom character to CGRAM from an image
+    ///
+    /// Pixels outside of the top-left 16x16 pixels of the image are ignored.
+    pub fn upload_glyph_image<Delay: DelayNs>(
+        &mut self,
+        slot: CgramSlot,
+        image: &ImageRaw<BinaryColor>,
+        delay: &mut Delay,
+    ) -> Result<(), Error<CommError, PinError>> {
+        let mut glyph = Glyph([0; 16]);
+        Image::new(image, Point::zero()).draw(&mut glyph).ok();
+        self.upload_glyph(slot, &glyph.0, delay)
+    }
+}
+
+/// Draw target for rendering images into a CGRAM character.
+#[cfg(feature = "graphics")]
+struct Glyph([u16; 16]);
+
+#[cfg(feature = "graphics")]
+impl OriginDimensions for Glyph {
+    fn size(&self) -> Size {
+        Size::new(16, 16)
+    }
+}
+
+#[cfg(feature = "graphics")]
+impl DrawTarget for Glyph {
+    type Error = core::convert::Infallible;
+    type Color = BinaryColor;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(coord, color) in pixels {
+            if (0..16).contains(&coord.x) && (0..16).contains(&coord.y) {
+                let mask = 0x8000 >> coord.x;
+                match color {
+                    BinaryColor::On => self.0[coord.y as usize] |= mask,
+                    BinaryColor::Off => self.0[coord.y as usize] &= !mask,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Layout128x32, Layout128x64, Layout192x32, Layout256x32};
+
+    #[test]
+    fn line_addresses() {
+        assert_eq!(line_address::<Layout128x64>(0), 0x00);
+        assert_eq!(line_address::<Layout128x64>(1), 0x10);
+        assert_eq!(line_address::<Layout128x64>(2), 0x08);
+        assert_eq!(line_address::<Layout128x64>(3), 0x18);
+        assert_eq!(line_address::<Layout128x32>(1), 0x10);
+        assert_eq!(line_address::<Layout192x32>(1), 0x10);
+        assert_eq!(line_address::<Layout256x32>(1), 0x10);
+    }
+
+    #[test]
+    fn reverse_rows() {
+        assert_eq!(reverse_row::<Layout128x64>(0), 0);
+        assert_eq!(reverse_row::<Layout128x64>(1), 1);
+        assert_eq!(reverse_row::<Layout128x64>(2), 0);
+        assert_eq!(reverse_row::<Layout128x64>(3), 1);
+        assert_eq!(reverse_row::<Layout128x32>(1), 1);
+        assert_eq!(reverse_row::<Layout256x32>(1), 1);
+    }
+}
@@ -0,0 +1,71 @@
+//! Delays used when talking to the controller.
+
+/// Timing profile for initialization and instructions
+///
+/// The defaults work with genuine ST7920 controllers.
+/// Some clones need longer waits, fast parts may run fine with shorter ones.
+///
+/// # Examples
+///
+/// ```no_run
+/// use st7920::{Timing, ST7920};
+///
+/// # use embedded_hal::{delay::DelayNs, digital::OutputPin, spi::SpiDevice};
+/// # fn example<SPI, P, E, PE>(spi: SPI, reset: P, cs: P, mut delay: impl DelayNs) -> Result<(), st7920::Error<E, PE>>
+/// # where SPI: SpiDevice<Error = E>, P: OutputPin<Error = PE>, PE: embedded_hal::digital::Error {
+/// let timing = Timing {
+///     reset_ms: 100,
+///     flush_us: 80,
+///     ..Default::default()
+/// };
+/// let mut st7920 = ST7920::new(spi, reset, Some(cs), false).with_timing(timing);
+/// st7920.init(&mut delay)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Timing {
+    /// Length of the reset pulse and wait after the reset, in milliseconds
+    pub reset_ms: u32,
+
+    /// Wait after asserting chip select and before releasing it, in microseconds
+    pub cs_us: u32,
+
+    /// Wait after the first instruction after a reset, in microseconds
+    pub function_set_us: u32,
+
+    /// Wait after most instructions, in microseconds
+    pub command_us: u32,
+
+    /// Wait after clearing the display, in microseconds
+    pub clear_us: u32,
+
+    /// Wait during initialization after clearing the display
+    /// and after selecting the extended instruction set, in milliseconds
+    pub init_ms: u32,
+
+    /// Wait during initialization after turning the graphics display on, in milliseconds
+    pub graphics_on_ms: u32,
+
+    /// Wait between the instructions of a flush, in microseconds
+    ///
+    /// The default covers the 72 µs execution time of an instruction.
+    /// With 0, the address and data of a row are sent in one go, which is faster
+    /// and works if the bus is slow enough for the controller to keep up.
+    pub flush_us: u32,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing {
+            reset_ms: 40,
+            cs_us: 1,
+            function_set_us: 200,
+            command_us: 100,
+            clear_us: 2_000,
+            init_ms: 10,
+            graphics_on_ms: 100,
+            flush_us: 72,
+        }
+    }
+}